@@ -1,10 +1,12 @@
 use {
     crate::{
-        InteractState, MOUSE_OVERLAY_PREFIX, RectDragStatus, SourceMarkers, TabOpen,
+        InteractState, MOUSE_OVERLAY_PREFIX, Pick, RectDrag, RectDragStatus, SourceMarkers,
+        TabOpen,
         config::Config,
-        coords::{VideoDim, VideoMag, VideoPos, VideoVector},
+        coords::{Src, VideoDim, VideoMag, VideoPos, VideoRect, VideoVector},
         mpv::{Mpv, MpvEvent, commands as c, properties as p},
         overlay::{self, draw_overlay},
+        playlist::Playlist,
         present::Present,
         sfml_integ::VideoPosSfExt as _,
         subs::{SubsState, TrackingState},
@@ -46,6 +48,12 @@ pub struct AppState {
     pub present: Option<Present>,
     pub video_area_max_dim: VideoDim<crate::coords::Present>,
     pub pos_string: String,
+    /// Colour of the pixel under the mouse, if it's over the video
+    pub hover_rgb: Option<crate::color::Rgb>,
+    /// Another file (usually an encoded output) shown next to the source
+    pub compare: Option<crate::compare::Compare>,
+    /// Files opened together from a folder or playlist
+    pub playlist: Playlist,
     pub overlay_show: bool,
 }
 
@@ -74,16 +82,13 @@ impl AppState {
             }),
             source_markers: SourceMarkers::default(),
             interact: InteractState::default(),
-            src: crate::source::Info {
-                dim: VideoDim::new(0, 0),
-                w_h_ratio: 1.0,
-                duration: 0.0,
-                time_pos: 0.0,
-                path: String::new(),
-            },
+            src: crate::source::Info::new(String::new()),
+            playlist: Playlist::default(),
             present: None,
             video_area_max_dim: VideoDim::<crate::coords::Present>::new(0, 0),
             pos_string: String::from(MOUSE_OVERLAY_PREFIX),
+            hover_rgb: None,
+            compare: None,
             overlay_show: true,
             texts: Vec::new(),
         }
@@ -98,6 +103,13 @@ impl App {
         {
             return;
         }
+        if code == Key::Escape && self.state.interact.pick.is_some() {
+            self.state.interact.pick = None;
+            return;
+        }
+        if self.nudge_active_rect(code, ctrl) {
+            return;
+        }
         match code {
             Key::Tab => self.state.overlay_show ^= true,
             Key::Space => {
@@ -154,6 +166,48 @@ impl App {
         }
     }
 
+    /// Arrow keys move the active rect (resize with ctrl, by 10 pixels with shift)
+    ///
+    /// Returns whether the key was used
+    fn nudge_active_rect(&mut self, code: Key, ctrl: bool) -> bool {
+        let Some(marker) = self
+            .state
+            .interact
+            .active_rect
+            .and_then(|idx| self.state.source_markers.rects.get_mut(idx))
+        else {
+            return false;
+        };
+        let step = if self.input.key_down(Key::LShift) {
+            10
+        } else {
+            1
+        };
+        // Smaller steps would just get rounded away
+        let step = step.max(marker.constraints.align);
+        let (dx, dy) = match code {
+            Key::Left => (-step, 0),
+            Key::Right => (step, 0),
+            Key::Up => (0, -step),
+            Key::Down => (0, step),
+            Key::Escape => {
+                self.state.interact.active_rect = None;
+                return true;
+            }
+            _ => return false,
+        };
+        let mut rect = marker.rect;
+        if ctrl {
+            rect.dim.x = (rect.dim.x + dx).max(0);
+            rect.dim.y = (rect.dim.y + dy).max(0);
+        } else {
+            rect.pos.x += dx;
+            rect.pos.y += dy;
+        }
+        marker.rect = marker.constraints.apply(rect, self.state.src.dim, None);
+        true
+    }
+
     pub fn do_frame(&mut self, font: &Font) {
         if let Some(ev) = self.mpv.poll_and_handle_event() {
             match ev {
@@ -164,7 +218,14 @@ impl App {
                         VideoDim::new(actual_video_w as VideoMag, actual_video_h as VideoMag);
                     eprintln!("Video reconfig {:#?}", self.state.src.dim);
                     self.state.present = Present::new(self.state.src.dim.as_present());
-                    self.state.src.w_h_ratio = actual_video_w as f64 / actual_video_h as f64
+                    self.state.src.w_h_ratio = actual_video_w as f64 / actual_video_h as f64;
+                    let duration = self.mpv.get_property::<p::Duration>().unwrap_or(0.0);
+                    self.state.src.thumbnails.generate(
+                        &self.state.src.path,
+                        duration,
+                        self.state.src.dim,
+                        &self.cfg.tools,
+                    );
                 }
                 MpvEvent::Idle | MpvEvent::PlaybackRestart => {}
                 MpvEvent::FileLoaded => {
@@ -172,6 +233,19 @@ impl App {
                     if let Some(subs) = &mut self.state.subs {
                         subs.tracking = TrackingState::default();
                     }
+                    if let Some(path) = self.mpv.get_property::<p::Path>() {
+                        self.state.playlist.switch_markers(
+                            &self.state.src.path,
+                            path,
+                            &mut self.state.source_markers,
+                        );
+                        self.state.src.path = path.to_owned();
+                        self.state.src.keyframes.index(path, &self.cfg.tools);
+                        self.state.src.waveform.generate(path, &self.cfg.tools);
+                        self.state.src.scenes = Default::default();
+                        self.state.src.silence = Default::default();
+                        self.state.src.black = Default::default();
+                    }
                 }
                 MpvEvent::Seek => {
                     if let Some(subs) = &mut self.state.subs {
@@ -180,6 +254,24 @@ impl App {
                 }
             }
         }
+        if let Err(e) = self.state.src.keyframes.poll() {
+            eprintln!("Failed to index keyframes: {e}");
+        }
+        if let Err(e) = self.state.src.thumbnails.poll() {
+            eprintln!("Failed to generate thumbnails: {e}");
+        }
+        if let Err(e) = self.state.src.waveform.poll() {
+            eprintln!("Failed to decode audio waveform: {e}");
+        }
+        if let Err(e) = self.state.src.scenes.poll() {
+            eprintln!("Failed to detect scene changes: {e}");
+        }
+        if let Err(e) = self.state.src.silence.poll() {
+            eprintln!("Failed to detect silence: {e}");
+        }
+        if let Err(e) = self.state.src.black.poll() {
+            eprintln!("Failed to detect black frames: {e}");
+        }
         self.input.start_frame();
         let mut collected_events = Vec::new();
         while let Some(event) = self.rw.poll_event() {
@@ -211,7 +303,19 @@ impl App {
                 .map_or(VideoVector::new(0, 0), |present| present.dim),
         );
         self.state.src.duration = self.mpv.get_property::<p::Duration>().unwrap_or(0.0);
-        self.state.src.time_pos = self.mpv.get_property::<p::TimePos>().unwrap_or(0.0);
+        let time_pos = self.mpv.get_property::<p::TimePos>().unwrap_or(0.0);
+        // Only follow while time moves, so animated rects can be repositioned while paused
+        if time_pos != self.state.src.time_pos {
+            let dragged = self.state.interact.rect_drag.as_ref().map(|drag| drag.idx);
+            self.state
+                .source_markers
+                .follow_rect_keyframes(time_pos, dragged);
+        }
+        self.state.src.time_pos = time_pos;
+        if let Some(compare) = &mut self.state.compare {
+            compare.sync(&self.mpv, &self.state.src);
+        }
+        self.state.src.fps = self.mpv.get_property::<p::ContainerFps>().unwrap_or(0.0);
         if let Some(drag) = &self.state.interact.rect_drag {
             match drag.status {
                 RectDragStatus::Init => {}
@@ -221,6 +325,21 @@ impl App {
                     self.state.source_markers.rects[drag.idx].rect.dim.y =
                         src_mouse_pos.y - self.state.source_markers.rects[drag.idx].rect.pos.y;
                 }
+                RectDragStatus::Manipulate {
+                    handle,
+                    origin,
+                    orig_rect,
+                } => {
+                    let marker = &mut self.state.source_markers.rects[drag.idx];
+                    let rect = handle.apply(
+                        orig_rect,
+                        src_mouse_pos.x - origin.x,
+                        src_mouse_pos.y - origin.y,
+                    );
+                    marker.rect = marker
+                        .constraints
+                        .apply(rect, self.state.src.dim, Some(handle));
+                }
             }
         }
         if let Some(orig_cur) = &self.state.interact.pan_cursor_origin
@@ -257,6 +376,15 @@ impl App {
             src_mouse_pos.x, src_mouse_pos.y,
         )
         .unwrap();
+        // The pixel buffer is from the previous frame, but that's close enough for inspecting
+        self.state.hover_rgb = self.state.present.as_ref().and_then(|present| {
+            let pan = self.state.interact.pan_pos;
+            self.mpv.pixel_at(
+                present.dim,
+                raw_mouse_pos.x - i32::from(pan.x),
+                raw_mouse_pos.y - i32::from(pan.y),
+            )
+        });
         self.rw.clear(Color::BLACK);
         if let Some(present) = self.state.present.as_mut() {
             let pixels = self.mpv.get_frame_as_pixels(present.dim);
@@ -267,9 +395,19 @@ impl App {
                 0,
                 0,
             );
-            let mut s = Sprite::with_texture(&present.texture);
-            s.set_position(self.state.interact.pan_pos.to_sf());
-            self.rw.draw_sprite(&s, &RenderStates::DEFAULT);
+            if let Some(compare) = &mut self.state.compare {
+                compare.draw(
+                    &mut self.rw,
+                    &present.texture,
+                    pixels,
+                    present.dim,
+                    self.state.interact.pan_pos.to_sf(),
+                );
+            } else {
+                let mut s = Sprite::with_texture(&present.texture);
+                s.set_position(self.state.interact.pan_pos.to_sf());
+                self.rw.draw_sprite(&s, &RenderStates::DEFAULT);
+            }
         }
         if self.state.overlay_show {
             draw_overlay(&mut self.rw, &self.state, &self.state.pos_string, font);
@@ -300,12 +438,7 @@ impl App {
 
     /// Handle events after the egui ui
     fn handle_delayed_event(&mut self, event: Event, wants_kb: bool, wants_ptr: bool) {
-        overlay::handle_event(
-            &event,
-            &self.mpv,
-            &self.state.src,
-            self.state.video_area_max_dim,
-        );
+        overlay::handle_event(&event, &self.mpv, &mut self.state);
         match event {
             Event::KeyPressed { code, ctrl, .. } => {
                 if !wants_kb {
@@ -324,18 +457,68 @@ impl App {
                 if wants_ptr {
                     break 'block;
                 }
+                let pos = VideoPos::from_present(x, y, self.state.src.dim, present.dim);
                 // Video pan drag is shift + drag
                 if !self.input.key_down(Key::LShift) {
+                    // Clicks on the timeline are handled by the overlay
+                    if self.state.interact.rect_drag.is_some()
+                        || overlay::is_over_timeline(&self.state, x, y)
+                    {
+                        break 'block;
+                    }
+                    let markers = &mut self.state.source_markers;
+                    match self.state.interact.pick {
+                        Some(Pick::Point(idx)) => {
+                            if let Some(marker) = markers.points.get_mut(idx) {
+                                marker.pos = pos;
+                            }
+                            self.state.interact.pick = None;
+                            break 'block;
+                        }
+                        Some(Pick::PolygonVertices(idx)) => {
+                            if let Some(marker) = markers.polygons.get_mut(idx) {
+                                marker.points.push(pos);
+                            }
+                            break 'block;
+                        }
+                        Some(Pick::Color(idx)) => {
+                            let pan = self.state.interact.pan_pos;
+                            if let Some(marker) = markers.colors.get_mut(idx)
+                                && let Some(rgb) = self.mpv.pixel_at(
+                                    present.dim,
+                                    x - i32::from(pan.x),
+                                    y - i32::from(pan.y),
+                                )
+                            {
+                                marker.rgb = rgb;
+                            }
+                            self.state.interact.pick = None;
+                            break 'block;
+                        }
+                        None => {}
+                    }
+                    let grabbed = overlay::grab_rect(&self.state, x, y);
+                    self.state.interact.active_rect = grabbed.map(|(idx, _)| idx);
+                    if let Some((idx, handle)) = grabbed {
+                        self.ui_state.right_panel.selected_rect = Some(idx);
+                        self.state.interact.rect_drag = Some(RectDrag {
+                            idx,
+                            status: RectDragStatus::Manipulate {
+                                handle,
+                                origin: pos,
+                                orig_rect: self.state.source_markers.rects[idx].rect,
+                            },
+                        });
+                    }
                     break 'block;
                 }
-                let pos = VideoPos::from_present(x, y, self.state.src.dim, present.dim);
                 if let Some(drag) = &mut self.state.interact.rect_drag {
                     match drag.status {
                         RectDragStatus::Init => {
                             self.state.source_markers.rects[drag.idx].rect.pos = pos;
                             drag.status = RectDragStatus::ClickedTopLeft;
                         }
-                        RectDragStatus::ClickedTopLeft => {}
+                        RectDragStatus::ClickedTopLeft | RectDragStatus::Manipulate { .. } => {}
                     }
                 } else {
                     self.state.interact.pan_cursor_origin = Some(pos);
@@ -347,6 +530,14 @@ impl App {
                 x,
                 y,
             } => 'block: {
+                // Finish moving/resizing even if the mouse ended up over the ui
+                if let Some(RectDrag {
+                    status: RectDragStatus::Manipulate { .. },
+                    ..
+                }) = self.state.interact.rect_drag
+                {
+                    self.state.interact.rect_drag = None;
+                }
                 let Some(present) = self.state.present.as_ref() else {
                     break 'block;
                 };
@@ -358,19 +549,17 @@ impl App {
                     match drag.status {
                         RectDragStatus::Init => {}
                         RectDragStatus::ClickedTopLeft => {
-                            let rect = &mut self.state.source_markers.rects[drag.idx].rect;
-                            rect.dim.x = pos.x - rect.pos.x;
-                            rect.dim.y = pos.y - rect.pos.y;
-                            if rect.pos.x + rect.dim.x > self.state.src.dim.x {
-                                let diff = self.state.src.dim.x - rect.pos.x;
-                                rect.dim.x = diff;
-                            }
-                            if rect.pos.y + rect.dim.y > self.state.src.dim.y {
-                                let diff = self.state.src.dim.y - rect.pos.y;
-                                rect.dim.y = diff;
-                            }
+                            let marker = &mut self.state.source_markers.rects[drag.idx];
+                            let src_dim = self.state.src.dim;
+                            let clamp = |p: VideoPos<Src>| {
+                                VideoPos::new(p.x.clamp(0, src_dim.x), p.y.clamp(0, src_dim.y))
+                            };
+                            // Dragging up or left from the first click is fine too
+                            let rect = VideoRect::from_corners(clamp(marker.rect.pos), clamp(pos));
+                            marker.rect = marker.constraints.apply(rect, src_dim, None);
                             self.state.interact.rect_drag = None;
                         }
+                        RectDragStatus::Manipulate { .. } => {}
                     }
                 }
                 self.state.interact.pan_cursor_origin = None;
@@ -401,6 +590,8 @@ impl App {
                 TabOpen::Timespans => {
                     ui_state.right_panel.tab = crate::ui::right_panel::Tab::TimeSpans
                 }
+                TabOpen::Points => ui_state.right_panel.tab = crate::ui::right_panel::Tab::Points,
+                TabOpen::Colors => ui_state.right_panel.tab = crate::ui::right_panel::Tab::Colors,
             }
         }
         Self {
@@ -0,0 +1,32 @@
+use std::thread::JoinHandle;
+
+/// A job running on a background thread, polled from the ui every frame
+pub struct BgJob<T> {
+    handle: Option<JoinHandle<anyhow::Result<T>>>,
+}
+
+impl<T: Send + 'static> BgJob<T> {
+    pub fn spawn(f: impl FnOnce() -> anyhow::Result<T> + Send + 'static) -> Self {
+        Self {
+            handle: Some(std::thread::spawn(f)),
+        }
+    }
+    /// Returns the result of the job once it has finished.
+    ///
+    /// After the result has been taken, this always returns `None`.
+    pub fn poll(&mut self) -> Option<anyhow::Result<T>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        let handle = self.handle.take()?;
+        Some(
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Background job panicked"))),
+        )
+    }
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+}
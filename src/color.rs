@@ -0,0 +1,35 @@
+//! Formatting picked pixel colours
+
+pub type Rgb = [u8; 3];
+
+/// `#RRGGBB`
+pub fn hex(rgb: Rgb) -> String {
+    let [r, g, b] = rgb;
+    format!("#{r:02X}{g:02X}{b:02X}")
+}
+
+/// `0xRRGGBB`, the way ffmpeg options like `colorkey=color=` take it
+pub fn ffmpeg_hex(rgb: Rgb) -> String {
+    let [r, g, b] = rgb;
+    format!("0x{r:02X}{g:02X}{b:02X}")
+}
+
+/// Limited range BT.709 Y'CbCr, which is what most HD video is encoded as
+pub fn yuv(rgb: Rgb) -> [u8; 3] {
+    const KR: f64 = 0.2126;
+    const KB: f64 = 0.0722;
+    let [r, g, b] = rgb.map(|c| f64::from(c) / 255.0);
+    let y = KR * r + (1.0 - KR - KB) * g + KB * b;
+    let cb = (b - y) / (2.0 * (1.0 - KB));
+    let cr = (r - y) / (2.0 * (1.0 - KR));
+    [16.0 + 219.0 * y, 128.0 + 224.0 * cb, 128.0 + 224.0 * cr].map(|v| v.round() as u8)
+}
+
+#[test]
+fn test_color_formats() {
+    assert_eq!(hex([255, 0, 16]), "#FF0010");
+    assert_eq!(ffmpeg_hex([0, 255, 0]), "0x00FF00");
+    assert_eq!(yuv([255, 255, 255]), [235, 128, 128]);
+    assert_eq!(yuv([0, 0, 0]), [16, 128, 128]);
+    assert_eq!(yuv([255, 0, 0]), [63, 102, 240]);
+}
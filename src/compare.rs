@@ -0,0 +1,217 @@
+//! Comparing the source against another file (usually an encoded output) in a second mpv
+
+use {
+    crate::{
+        coords::{Present, Src, VideoDim, VideoMag},
+        mpv::{
+            Mpv, MpvEvent,
+            commands::LoadFile,
+            properties::{Height, KeepOpen, Mute, Pause, Speed, TimePos, Width},
+            property::YesNoAlways,
+        },
+        source,
+    },
+    egui_sf2g::sf2g::{
+        cpp::FBox,
+        graphics::{
+            Color, IntRect, RectangleShape, RenderTarget, RenderWindow, Shape, Sprite, Texture,
+            Transformable,
+        },
+        system::Vector2f,
+    },
+    sf2g::graphics::RenderStates,
+};
+
+/// How far (in seconds) the compared video may drift during playback before it's re-synced
+const DRIFT_TOLERANCE: f64 = 0.1;
+/// Space between the two videos in side by side mode
+const SIDE_GAP: f32 = 8.0;
+/// Differences are multiplied by this, so small ones are still visible
+const DIFF_GAIN: u8 = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    SideBySide,
+    /// Source on the left of the split, compared file on the right
+    Wipe,
+    /// Absolute difference of the two frames
+    Difference,
+}
+
+impl Mode {
+    pub const ALL: [Self; 3] = [Self::SideBySide, Self::Wipe, Self::Difference];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SideBySide => "Side by side",
+            Self::Wipe => "Wipe",
+            Self::Difference => "Difference",
+        }
+    }
+}
+
+pub struct Compare {
+    mpv: Mpv,
+    pub path: String,
+    pub mode: Mode,
+    /// Where the wipe splits the video, as a fraction of its width
+    pub wipe: f32,
+    /// Source time at which the compared file begins (e.g. for previews of a range)
+    pub offset: f64,
+    dim: VideoDim<Src>,
+    texture: FBox<Texture>,
+    tex_dim: VideoDim<Present>,
+    diff_buf: Vec<u8>,
+    /// Source time position the compared file was last seeked to
+    synced_to: f64,
+}
+
+impl Compare {
+    pub fn open(path: String) -> anyhow::Result<Self> {
+        let mut mpv = Mpv::new()?;
+        mpv.set_property::<KeepOpen>(YesNoAlways::Yes);
+        mpv.set_property::<Mute>(true);
+        mpv.set_property::<Pause>(true);
+        mpv.command_async(LoadFile { path: &path });
+        let Ok(texture) = Texture::new() else {
+            anyhow::bail!("Failed to create comparison texture");
+        };
+        Ok(Self {
+            mpv,
+            path,
+            mode: Mode::SideBySide,
+            wipe: 0.5,
+            offset: 0.0,
+            dim: VideoDim::new(0, 0),
+            texture,
+            tex_dim: VideoDim::new(0, 0),
+            diff_buf: Vec::new(),
+            synced_to: f64::NAN,
+        })
+    }
+    /// Follow the time position, pause state and speed of the source
+    pub fn sync(&mut self, src_mpv: &Mpv, src_info: &source::Info) {
+        if let Some(MpvEvent::VideoReconfig) = self.mpv.poll_and_handle_event() {
+            let w = self.mpv.get_property::<Width>().unwrap_or(0);
+            let h = self.mpv.get_property::<Height>().unwrap_or(0);
+            self.dim = VideoDim::new(w as VideoMag, h as VideoMag);
+            // Make sure the first frame is shown at the right position
+            self.synced_to = f64::NAN;
+        }
+        let paused = src_mpv.get_property::<Pause>().unwrap_or(true);
+        if self.mpv.get_property::<Pause>() != Some(paused) {
+            self.mpv.set_property::<Pause>(paused);
+        }
+        if let Some(speed) = src_mpv.get_property::<Speed>()
+            && self.mpv.get_property::<Speed>() != Some(speed)
+        {
+            self.mpv.set_property::<Speed>(speed);
+        }
+        let Some(time) = self.mpv.get_property::<TimePos>() else {
+            return;
+        };
+        // Frame times of the two files don't have to line up, so only seek when paused
+        // if the source moved, otherwise we'd keep seeking forever.
+        let target = src_info.time_pos - self.offset;
+        let out_of_sync = if paused {
+            src_info.time_pos != self.synced_to
+        } else {
+            (time - target).abs() > DRIFT_TOLERANCE
+        };
+        if out_of_sync && target >= 0.0 {
+            self.mpv.set_property::<TimePos>(target);
+            self.synced_to = src_info.time_pos;
+        }
+    }
+    /// Draw the source and the compared video according to the mode
+    ///
+    /// `src_pixels` is the source frame rendered at `present_dim`.
+    pub fn draw(
+        &mut self,
+        rw: &mut RenderWindow,
+        src_texture: &Texture,
+        src_pixels: &[u8],
+        present_dim: VideoDim<Present>,
+        pos: Vector2f,
+    ) {
+        let mut src_sprite = Sprite::with_texture(src_texture);
+        src_sprite.set_position(pos);
+        match self.mode {
+            Mode::SideBySide => {
+                rw.draw_sprite(&src_sprite, &RenderStates::DEFAULT);
+                // Keep the aspect ratio of the compared file, it might be cropped
+                let dim = if self.dim.y > 0 {
+                    let w =
+                        f32::from(present_dim.y) * f32::from(self.dim.x) / f32::from(self.dim.y);
+                    VideoDim::new(w as VideoMag, present_dim.y)
+                } else {
+                    present_dim
+                };
+                if !self.render(dim, None) {
+                    return;
+                }
+                let mut sprite = Sprite::with_texture(&self.texture);
+                sprite.set_position((pos.x + f32::from(present_dim.x) + SIDE_GAP, pos.y));
+                rw.draw_sprite(&sprite, &RenderStates::DEFAULT);
+            }
+            Mode::Wipe => {
+                rw.draw_sprite(&src_sprite, &RenderStates::DEFAULT);
+                if !self.render(present_dim, None) {
+                    return;
+                }
+                let (w, h) = (i32::from(present_dim.x), i32::from(present_dim.y));
+                let split = (self.wipe.clamp(0.0, 1.0) * w as f32) as i32;
+                let mut sprite = Sprite::with_texture(&self.texture);
+                sprite.set_texture_rect(IntRect::new(split, 0, w - split, h));
+                sprite.set_position((pos.x + split as f32, pos.y));
+                rw.draw_sprite(&sprite, &RenderStates::DEFAULT);
+                let mut line = RectangleShape::default();
+                line.set_fill_color(Color::WHITE);
+                line.set_size((1.0, h as f32));
+                line.set_position((pos.x + split as f32, pos.y));
+                rw.draw_rectangle_shape(&line, &RenderStates::DEFAULT);
+            }
+            Mode::Difference => {
+                if !self.render(present_dim, Some(src_pixels)) {
+                    rw.draw_sprite(&src_sprite, &RenderStates::DEFAULT);
+                    return;
+                }
+                let mut sprite = Sprite::with_texture(&self.texture);
+                sprite.set_position(pos);
+                rw.draw_sprite(&sprite, &RenderStates::DEFAULT);
+            }
+        }
+    }
+    /// Render the compared video into the texture, or its difference from `diff_against`
+    ///
+    /// Returns false if there is nothing to show.
+    fn render(&mut self, dim: VideoDim<Present>, diff_against: Option<&[u8]>) -> bool {
+        if dim.x <= 0 || dim.y <= 0 || self.dim.x == 0 {
+            return false;
+        }
+        let (w, h) = (dim.x as u32, dim.y as u32);
+        if (self.tex_dim.x, self.tex_dim.y) != (dim.x, dim.y) {
+            if self.texture.create(w, h).is_err() {
+                eprintln!("Failed to create comparison texture");
+                return false;
+            }
+            self.tex_dim = dim;
+        }
+        let pixels = self.mpv.get_frame_as_pixels(dim);
+        match diff_against {
+            Some(src) if src.len() == pixels.len() => {
+                self.diff_buf.clear();
+                self.diff_buf
+                    .extend(src.iter().zip(pixels).enumerate().map(|(i, (a, b))| {
+                        if i % 4 == 3 {
+                            255
+                        } else {
+                            a.abs_diff(*b).saturating_mul(DIFF_GAIN)
+                        }
+                    }));
+                self.texture.update_from_pixels(&self.diff_buf, w, h, 0, 0);
+            }
+            _ => self.texture.update_from_pixels(pixels, w, h, 0, 0),
+        }
+        true
+    }
+}
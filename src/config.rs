@@ -1,4 +1,5 @@
 use {
+    crate::ffmpeg::tools::Tools,
     recently_used_list::RecentlyUsedList,
     serde::{Deserialize, Serialize},
     std::{
@@ -12,6 +13,9 @@ pub struct Config {
     pub recently_used_list: RecentlyUsedList<String>,
     #[serde(default)]
     pub vo_preset: HashMap<String, VideoOutPreset>,
+    /// Which ffmpeg and ffprobe to run, and how
+    #[serde(default)]
+    pub tools: Tools,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -106,6 +106,13 @@ pub struct VideoRect<Space> {
     pub dim: VideoDim<Space>,
 }
 
+impl<Space> Clone for VideoRect<Space> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<Space> Copy for VideoRect<Space> {}
+
 impl<Kind> VideoRect<Kind> {
     pub fn new(x: VideoMag, y: VideoMag, w: VideoMag, h: VideoMag) -> Self {
         Self {
@@ -113,6 +120,19 @@ impl<Kind> VideoRect<Kind> {
             dim: VideoDim::new(w, h),
         }
     }
+    /// Rect spanning between two opposite corners, in any order
+    pub fn from_corners(a: VideoPos<Kind>, b: VideoPos<Kind>) -> Self {
+        Self::new(
+            a.x.min(b.x),
+            a.y.min(b.y),
+            a.x.abs_diff(b.x) as VideoMag,
+            a.y.abs_diff(b.y) as VideoMag,
+        )
+    }
+    /// The corner opposite of `pos`
+    pub fn end(&self) -> VideoPos<Kind> {
+        VideoPos::new(self.pos.x + self.dim.x, self.pos.y + self.dim.y)
+    }
 }
 
 impl<Space> VideoVector<Dim, Space> {
@@ -1,26 +1,50 @@
 use {
-    crate::{SourceMarkers, config::Config, source},
+    crate::{
+        RectMarker, SourceMarkers, TimeSpan, color,
+        config::Config,
+        coords::{Src, VideoMag, VideoPos},
+        rect_keyframes, source,
+    },
     egui_sf2g::egui::TextBuffer,
     std::{
         fmt::Write,
+        io::Read as _,
         num::ParseIntError,
-        process::{Child, Command, Stdio},
+        path::Path,
+        process::{Child, ExitStatus, Stdio},
     },
     thiserror::Error,
+    tools::Tools,
 };
 
+pub mod batch;
+pub mod concat;
+pub mod inputs;
+pub mod loudness;
+pub mod redact;
+pub mod tools;
+
 pub(crate) fn invoke(
     input: &str,
     markers: &SourceMarkers,
     texts: &[crate::text::Text],
     src_info: &source::Info,
+    inputs: &[inputs::Input],
     cfg: &Config,
+    overwrite: bool,
 ) -> anyhow::Result<Child> {
-    let resolved = resolve_arguments(input, markers, texts, src_info, cfg)?;
-    Ok(Command::new("ffmpeg")
+    let resolved = resolve_arguments(input, markers, texts, src_info, inputs, cfg)?;
+    let clobbered = clobbered_inputs(&resolved, &src_info.path, &cfg.tools);
+    if !clobbered.is_empty() {
+        anyhow::bail!("Refusing to overwrite input file: {}", clobbered.join(", "));
+    }
+    Ok(cfg
+        .tools
+        .user_ffmpeg()?
+        // ffmpeg can't ask whether to overwrite without a terminal, so tell it up front
+        .args(["-nostdin", if overwrite { "-y" } else { "-n" }])
         .args(resolved)
-        // Always overwrite file, otherwise it just hangs because it can't ask y/n question
-        .arg("-y")
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?)
@@ -34,6 +58,8 @@ pub enum ResolveError {
     ShellParseError(#[from] shell_words::ParseError),
     #[error("Mising item: {name}")]
     MissingItem { name: String },
+    #[error("{name} is used as input index, but not passed with -i")]
+    InputNotPassed { name: String },
     #[error("Format error: {0}")]
     FmtError(#[from] std::fmt::Error),
 }
@@ -43,19 +69,646 @@ pub fn resolve_arguments(
     markers: &SourceMarkers,
     texts: &[crate::text::Text],
     src_info: &source::Info,
+    inputs: &[inputs::Input],
     cfg: &Config,
 ) -> Result<Vec<String>, ResolveError> {
     let words = shell_words::split(input)?;
+    let input_indices = input_indices(&words, inputs);
     let mut out = Vec::new();
-    for word in words {
-        let tokens = tokenize_word(&word)?;
+    for word in &words {
+        let tokens = tokenize_word(word)?;
         out.extend_from_slice(&resolve_word_tokens(
-            &tokens, markers, texts, src_info, cfg,
+            &tokens,
+            markers,
+            texts,
+            src_info,
+            inputs,
+            &input_indices,
+            cfg,
         )?);
     }
     Ok(out)
 }
 
+/// The ffmpeg input index of each input passed as `-i {i}` or `-i {i.x}`
+///
+/// The key is the position of the extra input, or `None` for the source.
+fn input_indices(words: &[String], inputs: &[inputs::Input]) -> Vec<(Option<usize>, usize)> {
+    let mut indices = Vec::new();
+    let mut ffmpeg_idx = 0;
+    for [opt, val] in words.array_windows() {
+        if opt != "-i" {
+            continue;
+        }
+        if let Ok(tokens) = tokenize_word(val)
+            && let [Token::SubsInput(name)] = tokens[..]
+            && let Ok(key) = input_key(inputs, name)
+        {
+            indices.push((key, ffmpeg_idx));
+        }
+        ffmpeg_idx += 1;
+    }
+    indices
+}
+
+/// `None` for the source (empty name), otherwise the position of the extra input
+fn input_key(inputs: &[inputs::Input], name: &str) -> Result<Option<usize>, ResolveError> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+    inputs::find(inputs, name).map(|(idx, _)| Some(idx))
+}
+
+/// Call `f` for each substitution token in `input`, skipping words that fail to parse
+fn for_each_token(input: &str, mut f: impl FnMut(Token)) {
+    let Ok(words) = shell_words::split(input) else {
+        return;
+    };
+    for word in &words {
+        let Ok(tokens) = tokenize_word(word) else {
+            continue;
+        };
+        tokens.into_iter().for_each(&mut f);
+    }
+}
+
+/// Names of the timespans used in `input` that don't begin on a keyframe, if video is stream copied
+///
+/// Stream copy can only cut at keyframes, so these cuts would produce a different range than shown.
+pub fn unaligned_copy_cuts(
+    input: &str,
+    resolved: &[String],
+    markers: &SourceMarkers,
+    src_info: &source::Info,
+) -> Vec<String> {
+    let mut names = Vec::new();
+    if !copies_video(resolved) {
+        return names;
+    }
+    for_each_token(input, |tok| {
+        if let Token::SubsTimespan(name) = tok
+            && let Some(marker) = markers.timespans.iter().find(|m| m.name == name)
+            && !src_info
+                .keyframes
+                .is_aligned(marker.timespan.begin, src_info.keyframe_tolerance())
+        {
+            names.push(name.to_owned());
+        }
+    });
+    names
+}
+
+/// Warnings for rects used in `input` that the output pixel format can't represent
+///
+/// For example yuv420p halves the chroma resolution, so crops need even sizes and offsets.
+pub fn rect_pix_fmt_warnings(
+    input: &str,
+    resolved: &[String],
+    markers: &SourceMarkers,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let Some(pix_fmt) = resolved
+        .array_windows()
+        .filter_map(|[opt, val]| (opt == "-pix_fmt").then_some(val.as_str()))
+        .last()
+    else {
+        return warnings;
+    };
+    let (sub_x, sub_y) = chroma_subsampling(pix_fmt);
+    for_each_token(input, |tok| {
+        if let Token::SubsRect(name) = tok
+            && let Some(marker) = markers.rects.iter().find(|m| m.name == name)
+        {
+            let r = &marker.rect;
+            if r.dim.x % sub_x != 0 || r.pos.x % sub_x != 0 {
+                warnings.push(format!(
+                    "Rect {name}: {pix_fmt} needs x and width to be multiples of {sub_x} \
+                     (x: {}, w: {})",
+                    r.pos.x, r.dim.x
+                ));
+            }
+            if r.dim.y % sub_y != 0 || r.pos.y % sub_y != 0 {
+                warnings.push(format!(
+                    "Rect {name}: {pix_fmt} needs y and height to be multiples of {sub_y} \
+                     (y: {}, h: {})",
+                    r.pos.y, r.dim.y
+                ));
+            }
+        }
+    });
+    warnings
+}
+
+/// Horizontal and vertical chroma subsampling factors of a pixel format
+fn chroma_subsampling(pix_fmt: &str) -> (VideoMag, VideoMag) {
+    match pix_fmt {
+        "nv12" | "nv21" | "p010le" | "p010be" | "p016le" => (2, 2),
+        "nv16" | "yuyv422" | "uyvy422" => (2, 1),
+        _ if pix_fmt.contains("420") => (2, 2),
+        _ if pix_fmt.contains("422") => (2, 1),
+        _ if pix_fmt.contains("411") => (4, 1),
+        _ if pix_fmt.contains("410") => (4, 4),
+        _ => (1, 1),
+    }
+}
+
+/// ffmpeg options that don't take a value
+const VALUELESS_OPTS: &[&str] = &[
+    "-y",
+    "-n",
+    "-nostdin",
+    "-stdin",
+    "-hide_banner",
+    "-an",
+    "-vn",
+    "-sn",
+    "-dn",
+    "-shortest",
+    "-stats",
+    "-nostats",
+    "-copyts",
+    "-start_at_zero",
+    "-re",
+    "-benchmark",
+    "-xerror",
+    "-accurate_seek",
+    "-noaccurate_seek",
+    "-autorotate",
+    "-noautorotate",
+    "-bitexact",
+    "-ignore_unknown",
+    "-copy_unknown",
+];
+
+/// Output files of an ffmpeg invocation
+///
+/// These are the arguments that aren't options, option values or inputs.
+/// Pipes are skipped, and `-f tee` outputs are split into their parts.
+pub fn output_paths(args: &[String]) -> Vec<String> {
+    let mut outputs = Vec::new();
+    let mut prev = 0;
+    for idx in output_indices(args) {
+        let arg = &args[idx];
+        let muxer = args[prev..idx]
+            .array_windows()
+            .filter_map(|[opt, val]| (opt == "-f").then_some(val.as_str()))
+            .last();
+        if muxer == Some("tee") {
+            for part in arg.split('|') {
+                // Per output options, like `[f=mpegts]out.ts`
+                let path = part.rsplit_once(']').map_or(part, |(_, path)| path);
+                outputs.push(path.to_owned());
+            }
+        } else {
+            outputs.push(arg.clone());
+        }
+        prev = idx + 1;
+    }
+    outputs.retain(|path| path != "-" && !path.starts_with("pipe:"));
+    outputs
+}
+
+/// Indices of the arguments that are outputs: not options, option values or inputs
+fn output_indices(args: &[String]) -> Vec<usize> {
+    let mut indices = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg.len() > 1 && arg.starts_with('-') {
+            if !VALUELESS_OPTS.contains(&arg.as_str()) {
+                // Skip the value
+                i += 1;
+            }
+        } else {
+            indices.push(i);
+        }
+        i += 1;
+    }
+    indices
+}
+
+/// Indices of the outputs of a command that hasn't been resolved yet
+///
+/// Timespans and video output presets turn into options, so they aren't outputs.
+fn template_output_indices(words: &[String]) -> Vec<usize> {
+    output_indices(words)
+        .into_iter()
+        .filter(|&idx| {
+            !matches!(
+                tokenize_word(&words[idx]).as_deref(),
+                Ok([Token::SubsTimespan(_) | Token::SubsVoPreset(_)])
+            )
+        })
+        .collect()
+}
+
+/// Outputs of `args` that are also one of its inputs, or the source file
+pub fn clobbered_inputs(args: &[String], src_path: &str, tools: &Tools) -> Vec<String> {
+    let inputs: Vec<&str> = args
+        .array_windows()
+        .filter_map(|[opt, val]| (opt == "-i").then_some(val.as_str()))
+        .chain(std::iter::once(src_path))
+        .filter(|path| !path.is_empty())
+        .collect();
+    output_paths(args)
+        .into_iter()
+        .filter(|out| inputs.iter().any(|input| same_file(out, input, tools)))
+        .collect()
+}
+
+/// Whether two paths are the same file, even if they are spelled differently
+fn same_file(a: &str, b: &str, tools: &Tools) -> bool {
+    if a == b {
+        return true;
+    }
+    match (
+        std::fs::canonicalize(tools.in_working_dir(a)),
+        std::fs::canonicalize(tools.in_working_dir(b)),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[test]
+fn test_clobbered_inputs() {
+    let clobbered = |cmd, src_path| {
+        clobbered_inputs(
+            &shell_words::split(cmd).unwrap(),
+            src_path,
+            &Tools::default(),
+        )
+    };
+    assert_eq!(
+        clobbered("-i a.mp4 -i b.wav -map 0 b.wav c.mp4", "src.mp4"),
+        ["b.wav"]
+    );
+    assert_eq!(
+        clobbered("-f concat -i list.txt -c copy src.mp4", "src.mp4"),
+        ["src.mp4"]
+    );
+    assert!(clobbered("-i a.mp4 out.mp4", "").is_empty());
+}
+
+/// `args`, but with every output replaced by a single frame (or a moment of audio) to the null muxer
+///
+/// This is quick to run, but still sets up inputs, filters and encoders, so it catches most mistakes.
+/// Two-pass options are dropped, so a dry run doesn't overwrite the log of a real first pass.
+pub fn dry_run_args(args: &[String]) -> Vec<String> {
+    let outputs = output_indices(args);
+    let mut dry = vec![
+        "-hide_banner".into(),
+        "-nostdin".into(),
+        "-v".into(),
+        "error".into(),
+    ];
+    let is_pass_opt = |arg: &str| {
+        ["-pass", "-passlogfile"]
+            .iter()
+            .any(|opt| arg == *opt || arg.strip_prefix(opt).is_some_and(|s| s.starts_with(':')))
+    };
+    let mut skip_value = false;
+    for (i, arg) in args.iter().enumerate() {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if is_pass_opt(arg) {
+            skip_value = true;
+        } else if outputs.contains(&i) {
+            // `-t` keeps outputs without video from decoding the whole input
+            dry.extend(["-frames:v", "1", "-t", "0.1", "-f", "null", "-"].map(String::from));
+        } else {
+            dry.push(arg.clone());
+        }
+    }
+    dry
+}
+
+#[test]
+fn test_dry_run_args() {
+    let args = shell_words::split("-i in.mp4 -vf scale=100:-2 -f mp4 out.mp4").unwrap();
+    assert_eq!(
+        dry_run_args(&args).join(" "),
+        "-hide_banner -nostdin -v error -i in.mp4 -vf scale=100:-2 -f mp4 \
+         -frames:v 1 -t 0.1 -f null -"
+    );
+    let args = shell_words::split("-i in.wav -af volume=2 a.flac -map 0 b.mkv").unwrap();
+    assert_eq!(
+        dry_run_args(&args).join(" "),
+        "-hide_banner -nostdin -v error -i in.wav -af volume=2 -frames:v 1 -t 0.1 -f null - \
+         -map 0 -frames:v 1 -t 0.1 -f null -"
+    );
+    let args =
+        shell_words::split("-i in.mp4 -c:v libx264 -pass:v 1 -passlogfile log -an -f null -")
+            .unwrap();
+    assert_eq!(
+        dry_run_args(&args).join(" "),
+        "-hide_banner -nostdin -v error -i in.mp4 -c:v libx264 -an -f null \
+         -frames:v 1 -t 0.1 -f null -"
+    );
+}
+
+/// Options that trim the input or output, which the preview range replaces
+const SEEK_OPTIONS: [&str; 4] = ["-ss", "-sseof", "-t", "-to"];
+
+/// Scales the preview down to at most 360 lines
+const PREVIEW_SCALE: &str = "scale=-2:'min(360,ih)'";
+
+/// `args` restricted to `range` of the first input, quickly encoded to `out_path` at low resolution
+///
+/// Only the first output is kept, and the command's own seeks are dropped.
+/// Timestamps are kept as they are in the source,
+/// so time based expressions (`enable=`, animated rects) still line up.
+/// Commands with a complex filter graph aren't scaled down, as that can't be added to with `-vf`.
+pub fn preview_args(args: &[String], range: TimeSpan, out_path: &str) -> Vec<String> {
+    let outputs = output_indices(args);
+    let end = outputs.first().map_or(args.len(), |&idx| idx);
+    let mut kept = Vec::new();
+    let mut iter = args[..end].iter();
+    while let Some(arg) = iter.next() {
+        if SEEK_OPTIONS.contains(&arg.as_str()) {
+            iter.next();
+        } else {
+            kept.push(arg.clone());
+        }
+    }
+    let is_vf = |arg: &String| ["-vf", "-filter:v", "-filter:v:0"].contains(&arg.as_str());
+    let has_complex = kept
+        .iter()
+        .any(|arg| arg == "-filter_complex" || arg == "-lavfi");
+    match kept.iter().rposition(is_vf) {
+        Some(idx) if idx + 1 < kept.len() => {
+            kept[idx + 1] = format!("{},{PREVIEW_SCALE}", kept[idx + 1]);
+        }
+        _ if !has_complex => kept.extend(["-vf".into(), PREVIEW_SCALE.into()]),
+        _ => {}
+    }
+    let mut preview = vec!["-hide_banner".into(), "-nostdin".into(), "-y".into()];
+    let mut seeked = false;
+    for arg in kept {
+        if arg == "-i" && !seeked {
+            preview.extend([
+                "-ss".into(),
+                range.begin.to_string(),
+                "-t".into(),
+                (range.end - range.begin).to_string(),
+                "-copyts".into(),
+            ]);
+            seeked = true;
+        }
+        preview.push(arg);
+    }
+    preview.extend(
+        [
+            "-c:v",
+            "libx264",
+            "-preset",
+            "ultrafast",
+            "-crf",
+            "30",
+            "-c:a",
+            "aac",
+            "-f",
+            "matroska",
+        ]
+        .map(String::from),
+    );
+    preview.push(out_path.to_owned());
+    preview
+}
+
+#[test]
+fn test_preview_args() {
+    let args = shell_words::split("-i in.mp4 -vf hflip -c:v libx265 a.mp4 -an b.mp4").unwrap();
+    assert_eq!(
+        preview_args(
+            &args,
+            TimeSpan {
+                begin: 2.0,
+                end: 5.5
+            },
+            "/tmp/p.mkv"
+        )
+        .join(" "),
+        "-hide_banner -nostdin -y -ss 2 -t 3.5 -copyts -i in.mp4 \
+         -vf hflip,scale=-2:'min(360,ih)' -c:v libx265 \
+         -c:v libx264 -preset ultrafast -crf 30 -c:a aac -f matroska /tmp/p.mkv"
+    );
+    let args = shell_words::split("-ss 60 -t 10 -i in.mp4 -i logo.png -t 5 out.mp4").unwrap();
+    assert_eq!(
+        preview_args(
+            &args,
+            TimeSpan {
+                begin: 2.0,
+                end: 5.5
+            },
+            "/tmp/p.mkv"
+        )
+        .join(" "),
+        "-hide_banner -nostdin -y -ss 2 -t 3.5 -copyts -i in.mp4 -i logo.png \
+         -vf scale=-2:'min(360,ih)' \
+         -c:v libx264 -preset ultrafast -crf 30 -c:a aac -f matroska /tmp/p.mkv"
+    );
+}
+
+/// Render a preview (see [`preview_args`])
+pub fn render_preview(
+    tools: &Tools,
+    args: &[String],
+    range: TimeSpan,
+    out_path: &str,
+) -> anyhow::Result<()> {
+    let out = tools
+        .user_ffmpeg()?
+        .args(preview_args(args, range, out_path))
+        .stdin(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        anyhow::bail!(
+            "Preview failed: {}",
+            stderr.lines().last().unwrap_or_default().trim()
+        );
+    }
+    Ok(())
+}
+
+/// Start a dry run (see [`dry_run_args`]), so it can be killed if the arguments change
+pub fn spawn_dry_run(tools: &Tools, args: &[String]) -> anyhow::Result<Child> {
+    Ok(tools
+        .user_ffmpeg()?
+        .args(dry_run_args(args))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?)
+}
+
+/// Result of a dry run that has exited, failing with ffmpeg's first error line
+pub fn dry_run_result(child: &mut Child, status: ExitStatus) -> anyhow::Result<()> {
+    if !status.success() {
+        let mut stderr = String::new();
+        if let Some(mut pipe) = child.stderr.take() {
+            pipe.read_to_string(&mut stderr)?;
+        }
+        let line = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("ffmpeg exited with an error");
+        anyhow::bail!("{}", line.trim());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_output_paths() {
+    let outs = |s: &str| output_paths(&shell_words::split(s).unwrap());
+    assert_eq!(outs("-i in.mp4 -c:v libx264 -y out.mp4"), ["out.mp4"]);
+    assert_eq!(
+        outs("-i in.mp4 -map \"[out]\" -an a.mkv -vn -f wav b.wav"),
+        ["a.mkv", "b.wav"]
+    );
+    assert_eq!(outs("-i in.mp4 -f null -"), [""; 0]);
+    assert_eq!(
+        outs("-i in.mp4 -f tee \"a.mkv|[f=mpegts:onfail=ignore]b.ts\""),
+        ["a.mkv", "b.ts"]
+    );
+}
+
+/// Duration of a media file in seconds
+pub fn probe_duration(tools: &Tools, path: &str) -> anyhow::Result<f64> {
+    let out = tools
+        .ffprobe()
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().parse()?)
+}
+
+/// The `-vf` filter chain of the (first) output, if there is one
+pub fn video_filter_chain(args: &[String]) -> Option<&str> {
+    first_output_option(args, &["-vf", "-filter:v", "-filter:v:0"])
+}
+
+/// The `-af` filter chain of the (first) output, if there is one
+pub fn audio_filter_chain(args: &[String]) -> Option<&str> {
+    first_output_option(args, &["-af", "-filter:a", "-filter:a:0"])
+}
+
+/// Value of the last of `names` given before the first output
+fn first_output_option<'a>(args: &'a [String], names: &[&str]) -> Option<&'a str> {
+    let end = output_indices(args).first().map_or(args.len(), |&idx| idx);
+    args[..end]
+        .array_windows()
+        .filter_map(|[opt, val]| names.contains(&opt.as_str()).then_some(val.as_str()))
+        .last()
+}
+
+/// A filter chain as a value for mpv's `vf` or `af` property
+///
+/// Uses mpv's `%len%` quoting, so the chain can contain any characters.
+pub fn mpv_lavfi(chain: &str) -> String {
+    format!("lavfi=graph=%{}%{chain}", chain.len())
+}
+
+#[test]
+fn test_video_filter_chain() {
+    let args = |s: &str| shell_words::split(s).unwrap();
+    assert_eq!(
+        video_filter_chain(&args("-i in.mp4 -vf crop=10:10:0:0,hflip out.mp4")),
+        Some("crop=10:10:0:0,hflip")
+    );
+    assert_eq!(
+        video_filter_chain(&args("-i in.mp4 out.mp4 -vf hflip out2.mp4")),
+        None
+    );
+    assert_eq!(
+        audio_filter_chain(&args("-i in.mp4 -af volume=2 -vf hflip out.mp4")),
+        Some("volume=2")
+    );
+    assert_eq!(mpv_lavfi("eq=gamma=2"), "lavfi=graph=%10%eq=gamma=2");
+}
+
+fn copies_video(args: &[String]) -> bool {
+    args.array_windows().any(|[opt, val]| {
+        val == "copy"
+            && matches!(
+                opt.as_str(),
+                "-c" | "-c:v" | "-c:v:0" | "-codec" | "-codec:v" | "-vcodec"
+            )
+    })
+}
+
+#[test]
+fn test_chroma_subsampling() {
+    assert_eq!(chroma_subsampling("yuv420p"), (2, 2));
+    assert_eq!(chroma_subsampling("yuv420p10le"), (2, 2));
+    assert_eq!(chroma_subsampling("yuvj422p"), (2, 1));
+    assert_eq!(chroma_subsampling("yuv444p"), (1, 1));
+    assert_eq!(chroma_subsampling("rgb24"), (1, 1));
+}
+
+#[test]
+fn test_copies_video() {
+    let args = |s: &str| shell_words::split(s).unwrap();
+    assert!(copies_video(&args("-i in.mp4 -c copy out.mp4")));
+    assert!(copies_video(&args("-i in.mp4 -c:v copy -c:a aac out.mp4")));
+    assert!(!copies_video(&args("-i in.mp4 -c:a copy out.mp4")));
+    assert!(!copies_video(&args("-i copy.mp4 out.mp4")));
+}
+
+/// The item called `name`, or a [`ResolveError::MissingItem`]
+fn find_named<'a, T>(
+    items: &'a [T],
+    name: &str,
+    get_name: impl Fn(&T) -> &String,
+) -> Result<&'a T, ResolveError> {
+    items
+        .iter()
+        .find(|item| get_name(item) == name)
+        .ok_or_else(|| ResolveError::MissingItem {
+            name: name.to_owned(),
+        })
+}
+
+/// Width, height, and x/y position of a rect marker (expressions of t for animated rects)
+///
+/// Filters like crop only evaluate the size once, so only the position of animated rects moves.
+/// The keyframe ui keeps the size the same across keyframes for this.
+fn rect_exprs(marker: &RectMarker) -> [String; 4] {
+    if marker.keyframes.len() > 1 {
+        let first = &marker.keyframes[0].rect;
+        [
+            first.dim.x.to_string(),
+            first.dim.y.to_string(),
+            format!(
+                "'{}'",
+                rect_keyframes::ffmpeg_expr(&marker.keyframes, |r| r.pos.x)
+            ),
+            format!(
+                "'{}'",
+                rect_keyframes::ffmpeg_expr(&marker.keyframes, |r| r.pos.y)
+            ),
+        ]
+    } else {
+        let r = &marker.rect;
+        [r.dim.x, r.dim.y, r.pos.x, r.pos.y].map(|v| v.to_string())
+    }
+}
+
 /// Takes a token stream of word tokens, and turns it into one more more resolved strings
 ///
 /// Example:
@@ -66,6 +719,8 @@ fn resolve_word_tokens(
     markers: &SourceMarkers,
     texts: &[crate::text::Text],
     src_info: &source::Info,
+    inputs: &[inputs::Input],
+    input_indices: &[(Option<usize>, usize)],
     cfg: &Config,
 ) -> Result<Vec<String>, ResolveError> {
     let mut resolved = Vec::new();
@@ -81,11 +736,8 @@ fn resolve_word_tokens(
                     .ok_or_else(|| ResolveError::MissingItem {
                         name: name.to_string(),
                     })?;
-                write!(
-                    &mut current_string,
-                    "{}:{}:{}:{}",
-                    marker.rect.dim.x, marker.rect.dim.y, marker.rect.pos.x, marker.rect.pos.y
-                )?;
+                let [w, h, x, y] = rect_exprs(marker);
+                write!(&mut current_string, "{w}:{h}:{x}:{y}")?;
             }
             Token::SubsTimespan(name) => {
                 let marker = markers
@@ -126,7 +778,47 @@ fn resolve_word_tokens(
                 );
                 current_string.push_str(&filt);
             }
-            Token::SubsInput => current_string.push_str(&src_info.path),
+            Token::SubsPoint(name) => {
+                let marker = find_named(&markers.points, name, |m| &m.name)?;
+                write!(&mut current_string, "{}:{}", marker.pos.x, marker.pos.y)?;
+            }
+            Token::SubsPolygon(name) => {
+                let marker = find_named(&markers.polygons, name, |m| &m.name)?;
+                write!(
+                    &mut current_string,
+                    "'{}'",
+                    polygon_geq_expr(&marker.points)
+                )?;
+            }
+            Token::SubsColor(name) => {
+                let marker = find_named(&markers.colors, name, |m| &m.name)?;
+                current_string.push_str(&color::ffmpeg_hex(marker.rgb));
+            }
+            Token::SubsConcat { names, audio } => {
+                let names: Vec<&str> = names.split(',').map(str::trim).collect();
+                current_string.push_str(&concat::filter_complex(&names, *audio, markers)?);
+            }
+            Token::SubsInput("") => current_string.push_str(&src_info.path),
+            Token::SubsInput(part) if PATH_PARTS.contains(part) => {
+                current_string.push_str(&path_part(&src_info.path, part));
+            }
+            Token::SubsInput(name) => {
+                current_string.push_str(&inputs::find(inputs, name)?.1.path);
+            }
+            Token::SubsOut(spec) => {
+                current_string.push_str(&out_path(&src_info.path, spec, Path::exists));
+            }
+            Token::SubsInputIndex(name) => {
+                let key = input_key(inputs, name)?;
+                let (_, ffmpeg_idx) =
+                    input_indices
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .ok_or_else(|| ResolveError::InputNotPassed {
+                            name: format!("{{n.{name}}}"),
+                        })?;
+                write!(&mut current_string, "{ffmpeg_idx}")?;
+            }
             Token::SubsVoPreset(name) => {
                 let preset = cfg
                     .vo_preset
@@ -151,6 +843,48 @@ fn resolve_word_tokens(
     Ok(resolved)
 }
 
+/// A `geq` expression that is 255 inside the polygon, and 0 outside
+///
+/// Uses the even-odd rule: count the edges a ray going right from the pixel crosses.
+fn polygon_geq_expr(points: &[VideoPos<Src>]) -> String {
+    let mut crossings = Vec::new();
+    for (i, a) in points.iter().enumerate() {
+        let b = &points[(i + 1) % points.len()];
+        // Horizontal edges are never crossed, and would divide by zero
+        if a.y == b.y {
+            continue;
+        }
+        crossings.push(format!(
+            "not(eq(gt({ay},Y),gt({by},Y)))*lt(X,({dx})*(Y-{ay})/({dy})+{ax})",
+            ax = a.x,
+            ay = a.y,
+            by = b.y,
+            dx = b.x - a.x,
+            dy = b.y - a.y,
+        ));
+    }
+    if crossings.is_empty() {
+        return "0".into();
+    }
+    format!("255*mod({},2)", crossings.join("+"))
+}
+
+#[test]
+fn test_polygon_geq_expr() {
+    assert_eq!(polygon_geq_expr(&[]), "0");
+    assert_eq!(
+        polygon_geq_expr(&[
+            VideoPos::new(0, 0),
+            VideoPos::new(10, 0),
+            VideoPos::new(0, 10)
+        ]),
+        "255*mod(\
+            not(eq(gt(0,Y),gt(10,Y)))*lt(X,(-10)*(Y-0)/(10)+10)+\
+            not(eq(gt(10,Y),gt(0,Y)))*lt(X,(0)*(Y-10)/(-10)+0)\
+        ,2)"
+    );
+}
+
 enum Status {
     Init,
     SubsBegin,
@@ -165,13 +899,26 @@ enum SubsType {
     TimeSpan,
     Text,
     Input,
+    /// ffmpeg input index of an input
+    InputIndex,
     VoPreset,
+    Point,
+    Polygon,
+    Color,
+    /// `{cat.a,b}` concatenates timespans, `{catv.a,b}` only their video
+    Concat {
+        audio: bool,
+    },
+    /// `{out:suffix.ext}`, an output path next to the input
+    Out,
 }
 
 struct ParseState {
     status: Status,
     subs_type: SubsType,
     token_begin: usize,
+    /// Where the category name (e.g. `poly`) begins
+    categ_begin: usize,
 }
 
 impl Default for ParseState {
@@ -180,6 +927,7 @@ impl Default for ParseState {
             status: Status::Init,
             subs_type: SubsType::Rect,
             token_begin: 0,
+            categ_begin: 0,
         }
     }
 }
@@ -214,6 +962,11 @@ fn tokenize_word(word: &'_ str) -> Result<Vec<Token<'_>>, ParseError> {
                     state.subs_type = SubsType::Input;
                     state.token_begin = i + 1;
                 }
+                b'n' => {
+                    state.status = Status::SubsMeat;
+                    state.subs_type = SubsType::InputIndex;
+                    state.token_begin = i + 1;
+                }
                 b'r' => {
                     state.status = Status::SubsCategAccess;
                     state.subs_type = SubsType::Rect;
@@ -230,10 +983,49 @@ fn tokenize_word(word: &'_ str) -> Result<Vec<Token<'_>>, ParseError> {
                     state.status = Status::SubsCategAccess;
                     state.subs_type = SubsType::VoPreset;
                 }
+                b'c' => {
+                    state.status = Status::SubsCategAccess;
+                    state.subs_type = SubsType::Color;
+                    state.categ_begin = i;
+                }
+                b'p' => {
+                    state.status = Status::SubsCategAccess;
+                    state.subs_type = SubsType::Point;
+                    state.categ_begin = i;
+                }
+                b'o' => {
+                    state.status = Status::SubsCategAccess;
+                    state.subs_type = SubsType::Out;
+                    state.categ_begin = i;
+                }
                 _ => return Err(ParseError::UnexpectedToken),
             },
             Status::SubsCategAccess => {
-                if byte == b'.' {
+                // `out` is followed by a colon instead, as its meat usually contains periods
+                if let SubsType::Out = state.subs_type {
+                    match byte {
+                        b':' if &word[state.categ_begin..i] == "out" => {
+                            state.token_begin = i + 1;
+                            state.status = Status::SubsMeat;
+                        }
+                        b':' | b'.' | b'}' => return Err(ParseError::UnexpectedToken),
+                        _ => {}
+                    }
+                } else if byte == b'.' {
+                    // `p` is a point, but `poly` is a polygon
+                    if let SubsType::Point = state.subs_type
+                        && &word[state.categ_begin..i] == "poly"
+                    {
+                        state.subs_type = SubsType::Polygon;
+                    }
+                    // Same for `c` being a color, and `cat`/`catv` a concatenation
+                    if let SubsType::Color = state.subs_type {
+                        match &word[state.categ_begin..i] {
+                            "cat" => state.subs_type = SubsType::Concat { audio: true },
+                            "catv" => state.subs_type = SubsType::Concat { audio: false },
+                            _ => {}
+                        }
+                    }
                     state.token_begin = i + 1;
                     state.status = Status::SubsMeat;
                 }
@@ -245,8 +1037,14 @@ fn tokenize_word(word: &'_ str) -> Result<Vec<Token<'_>>, ParseError> {
                         SubsType::Rect => Token::SubsRect(raw),
                         SubsType::TimeSpan => Token::SubsTimespan(raw),
                         SubsType::Text => Token::SubsText { idx: raw.parse()? },
-                        SubsType::Input => Token::SubsInput,
+                        SubsType::Input => Token::SubsInput(input_name(raw)?),
+                        SubsType::InputIndex => Token::SubsInputIndex(input_name(raw)?),
                         SubsType::VoPreset => Token::SubsVoPreset(raw),
+                        SubsType::Point => Token::SubsPoint(raw),
+                        SubsType::Polygon => Token::SubsPolygon(raw),
+                        SubsType::Color => Token::SubsColor(raw),
+                        SubsType::Concat { audio } => Token::SubsConcat { names: raw, audio },
+                        SubsType::Out => Token::SubsOut(raw),
                     };
                     tokens.push(tok);
                     state.token_begin = i + 1;
@@ -271,14 +1069,107 @@ fn tokenize_word(word: &'_ str) -> Result<Vec<Token<'_>>, ParseError> {
     Ok(tokens)
 }
 
+/// Parts of the source path, usable as `{i.stem}` etc. (for naming outputs after the input)
+pub const PATH_PARTS: &[&str] = &["stem", "ext", "dir", "name"];
+
+fn path_part(path: &str, part: &str) -> String {
+    let path = Path::new(path);
+    let os_part = match part {
+        "stem" => path.file_stem(),
+        "ext" => path.extension(),
+        "dir" => path.parent().map(|dir| dir.as_os_str()),
+        "name" => path.file_name(),
+        _ => None,
+    };
+    match os_part {
+        Some(os_part) if !os_part.is_empty() => os_part.to_string_lossy().into_owned(),
+        // Relative paths are relative to the working directory
+        _ if part == "dir" => ".".into(),
+        _ => String::new(),
+    }
+}
+
+/// Resolve `{out:suffix.ext}`: the input path with `suffix` added to the stem, and `ext` as extension
+///
+/// Without an extension in `spec`, the input's is kept. If the path is taken, a number is
+/// appended, so existing files (and the input itself) are never overwritten.
+fn out_path(input: &str, spec: &str, exists: impl Fn(&Path) -> bool) -> String {
+    let (suffix, ext) = match spec.rsplit_once('.') {
+        Some((suffix, ext)) => (suffix, ext.to_owned()),
+        None => (spec, path_part(input, "ext")),
+    };
+    let dir = Path::new(input).parent().unwrap_or(Path::new(""));
+    let stem = path_part(input, "stem");
+    let file_name = |n: u32| {
+        let num = if n > 1 {
+            format!("_{n}")
+        } else {
+            String::new()
+        };
+        if ext.is_empty() {
+            format!("{stem}{suffix}{num}")
+        } else {
+            format!("{stem}{suffix}{num}.{ext}")
+        }
+    };
+    let mut n = 1;
+    loop {
+        let path = dir.join(file_name(n));
+        if path != Path::new(input) && !exists(&path) {
+            return path.display().to_string();
+        }
+        n += 1;
+    }
+}
+
+#[test]
+fn test_path_part() {
+    assert_eq!(path_part("/videos/clip.final.mp4", "stem"), "clip.final");
+    assert_eq!(path_part("/videos/clip.final.mp4", "ext"), "mp4");
+    assert_eq!(path_part("/videos/clip.final.mp4", "dir"), "/videos");
+    assert_eq!(
+        path_part("/videos/clip.final.mp4", "name"),
+        "clip.final.mp4"
+    );
+    assert_eq!(path_part("clip.mp4", "dir"), ".");
+    assert_eq!(path_part("/videos/clip", "ext"), "");
+    let taken =
+        |path: &Path| path == Path::new("/v/a_cut.mkv") || path == Path::new("/v/a_cut_2.mkv");
+    assert_eq!(out_path("/v/a.mp4", "_small.mp4", taken), "/v/a_small.mp4");
+    assert_eq!(out_path("/v/a.mp4", "_cut.mkv", taken), "/v/a_cut_3.mkv");
+    assert_eq!(out_path("/v/a.mp4", "_cut", taken), "/v/a_cut.mp4");
+    // Never the input itself
+    assert_eq!(out_path("/v/a.mp4", ".mp4", |_| false), "/v/a_2.mp4");
+}
+
+/// `{i}` refers to the source, `{i.x}` to an extra input
+fn input_name(raw: &str) -> Result<&str, ParseError> {
+    if raw.is_empty() {
+        return Ok(raw);
+    }
+    raw.strip_prefix('.').ok_or(ParseError::UnexpectedToken)
+}
+
 #[derive(Debug, Clone)]
 enum Token<'a> {
     Raw(&'a str),
     SubsRect(&'a str),
     SubsTimespan(&'a str),
-    SubsText { idx: usize },
-    SubsInput,
+    SubsText {
+        idx: usize,
+    },
+    /// Empty name for the source
+    SubsInput(&'a str),
+    SubsInputIndex(&'a str),
     SubsVoPreset(&'a str),
+    SubsPoint(&'a str),
+    SubsPolygon(&'a str),
+    SubsColor(&'a str),
+    SubsConcat {
+        names: &'a str,
+        audio: bool,
+    },
+    SubsOut(&'a str),
 }
 
 #[test]
@@ -296,6 +1187,8 @@ fn test_resolve() {
             },
             name: "0".into(),
             color: [0., 0., 0.],
+            constraints: Default::default(),
+            keyframes: Vec::new(),
         }],
         timespans: vec![TimespanMarker {
             timespan: TimeSpan {
@@ -305,6 +1198,16 @@ fn test_resolve() {
             name: "0".into(),
             color: [0., 0., 0.],
         }],
+        points: vec![crate::PointMarker {
+            pos: VideoPos::new(12, 34),
+            name: "0".into(),
+            color: [0., 0., 0.],
+        }],
+        polygons: Vec::new(),
+        colors: vec![crate::ColorMarker {
+            rgb: [0, 255, 16],
+            name: "green".into(),
+        }],
     };
     let test_src_info = source::Info {
         dim: VideoDim::new(0, 0),
@@ -312,6 +1215,13 @@ fn test_resolve() {
         duration: 0.0,
         time_pos: 0.0,
         path: "/home/my_video.mp4".into(),
+        fps: 0.0,
+        keyframes: Default::default(),
+        thumbnails: Default::default(),
+        waveform: Default::default(),
+        scenes: Default::default(),
+        silence: Default::default(),
+        black: Default::default(),
     };
     let mut cfg = Config::default();
     cfg.vo_preset.insert(
@@ -324,10 +1234,11 @@ fn test_resolve() {
     );
     assert_eq!(
         resolve_arguments(
-            "-i {i} {t.0} crop={r.0} {v.custom}",
+            "-i {i} {t.0} crop={r.0} {v.custom} overlay={p.0} colorkey={c.green}",
             &test_markers,
             test_texts,
             &test_src_info,
+            &[],
             &cfg
         )
         .unwrap(),
@@ -342,7 +1253,33 @@ fn test_resolve() {
             "-pix_fmt".to_string(),
             "yuv420p".to_string(),
             "-c:v".to_string(),
-            "h265".to_string()
+            "h265".to_string(),
+            "overlay=12:34".to_string(),
+            "colorkey=0x00FF10".to_string()
+        ]
+    );
+    assert_eq!(
+        resolve_arguments(
+            "-filter_complex {catv.0}",
+            &test_markers,
+            test_texts,
+            &test_src_info,
+            &[],
+            &cfg
+        )
+        .unwrap(),
+        vec![
+            "-filter_complex".to_string(),
+            "[0:v]trim=start=10:end=20,setpts=PTS-STARTPTS[v0];[v0]concat=n=1:v=1:a=0[outv]"
+                .to_string()
         ]
     );
+    let resolve =
+        |input| resolve_arguments(input, &test_markers, test_texts, &test_src_info, &[], &cfg);
+    assert_eq!(
+        resolve("-i logo.png -i {i} -map {n}:a").unwrap(),
+        ["-i", "logo.png", "-i", "/home/my_video.mp4", "-map", "1:a"]
+    );
+    assert!(resolve("-map {n}:a").is_err());
+    assert!(resolve("-i {i.1}").is_err());
 }
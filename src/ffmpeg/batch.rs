@@ -0,0 +1,135 @@
+//! Running the same command for several files, one after another
+
+use {
+    super::{ResolveError, clobbered_inputs, tools::Tools},
+    crate::bg_job::BgJob,
+    std::process::Stdio,
+};
+
+pub enum Status {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+}
+
+pub struct Job {
+    pub path: String,
+    args: Vec<String>,
+    pub status: Status,
+}
+
+impl Job {
+    /// A job for `path`, which fails right away if its arguments couldn't be resolved,
+    /// or it would overwrite one of its inputs
+    pub fn new(path: String, args: Result<Vec<String>, ResolveError>, tools: &Tools) -> Self {
+        let clobbered = args
+            .as_ref()
+            .map_or(Vec::new(), |args| clobbered_inputs(args, &path, tools));
+        if !clobbered.is_empty() {
+            return Self {
+                path,
+                args: Vec::new(),
+                status: Status::Failed(format!(
+                    "Refusing to overwrite input file: {}",
+                    clobbered.join(", ")
+                )),
+            };
+        }
+        match args {
+            Ok(args) => Self {
+                path,
+                args,
+                status: Status::Pending,
+            },
+            Err(e) => Self {
+                path,
+                args: Vec::new(),
+                status: Status::Failed(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Batch {
+    pub jobs: Vec<Job>,
+    /// Index of the running job
+    running: Option<(usize, BgJob<()>)>,
+    /// Don't start any more jobs after the running one
+    pub stop_requested: bool,
+    /// What the jobs are run with, as configured when the batch started
+    tools: Tools,
+}
+
+impl Batch {
+    pub fn start(&mut self, jobs: Vec<Job>, tools: &Tools) {
+        self.jobs = jobs;
+        self.tools = tools.clone();
+        self.stop_requested = false;
+        self.poll();
+    }
+    /// Collect the result of the running job, and start the next pending one
+    pub fn poll(&mut self) {
+        if let Some((idx, job)) = &mut self.running {
+            let Some(result) = job.poll() else {
+                return;
+            };
+            self.jobs[*idx].status = match result {
+                Ok(()) => Status::Done,
+                Err(e) => Status::Failed(e.to_string()),
+            };
+            self.running = None;
+        }
+        if self.stop_requested {
+            return;
+        }
+        let Some(idx) = self
+            .jobs
+            .iter()
+            .position(|job| matches!(job.status, Status::Pending))
+        else {
+            return;
+        };
+        let job = &mut self.jobs[idx];
+        job.status = Status::Running;
+        let (args, tools) = (job.args.clone(), self.tools.clone());
+        self.running = Some((idx, BgJob::spawn(move || run(&tools, &args))));
+    }
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+    /// How many jobs succeeded and how many failed so far
+    pub fn counts(&self) -> (usize, usize) {
+        self.jobs
+            .iter()
+            .fold((0, 0), |(done, failed), job| match job.status {
+                Status::Done => (done + 1, failed),
+                Status::Failed(_) => (done, failed + 1),
+                Status::Pending | Status::Running => (done, failed),
+            })
+    }
+}
+
+/// Run ffmpeg to completion, failing with its last output line
+fn run(tools: &Tools, args: &[String]) -> anyhow::Result<()> {
+    let out = tools
+        .user_ffmpeg()?
+        // Nobody is around to confirm overwriting during a batch, so never do it
+        .args(["-hide_banner", "-nostdin", "-nostats", "-n"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        anyhow::bail!(
+            "{}",
+            stderr
+                .lines()
+                .last()
+                .unwrap_or("ffmpeg exited with an error")
+                .trim()
+        );
+    }
+    Ok(())
+}
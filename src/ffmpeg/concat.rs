@@ -0,0 +1,110 @@
+//! Joining several timespan markers of the source into one output
+
+use {
+    super::{ResolveError, find_named},
+    crate::{SourceMarkers, TimeSpan},
+    std::fmt::Write as _,
+};
+
+/// A `-filter_complex` graph that trims the named timespans out of input 0 and concatenates them
+///
+/// Outputs `[outv]`, and `[outa]` if `audio` is true.
+pub fn filter_complex(
+    names: &[impl AsRef<str>],
+    audio: bool,
+    markers: &SourceMarkers,
+) -> Result<String, ResolveError> {
+    let mut graph = String::new();
+    let mut concat_inputs = String::new();
+    for (i, span) in timespans(names, markers)?.into_iter().enumerate() {
+        let (begin, end) = (span.begin, span.end);
+        write!(
+            graph,
+            "[0:v]trim=start={begin}:end={end},setpts=PTS-STARTPTS[v{i}];"
+        )?;
+        write!(concat_inputs, "[v{i}]")?;
+        if audio {
+            write!(
+                graph,
+                "[0:a]atrim=start={begin}:end={end},asetpts=PTS-STARTPTS[a{i}];"
+            )?;
+            write!(concat_inputs, "[a{i}]")?;
+        }
+    }
+    write!(
+        graph,
+        "{concat_inputs}concat=n={}:v=1:a={}[outv]",
+        names.len(),
+        u8::from(audio)
+    )?;
+    if audio {
+        graph.push_str("[outa]");
+    }
+    Ok(graph)
+}
+
+/// A script for the concat demuxer, which joins the named timespans of `path` without re-encoding
+///
+/// Like with `-ss`, stream copied spans begin at the keyframe before their begin.
+pub fn demuxer_script(
+    path: &str,
+    names: &[impl AsRef<str>],
+    markers: &SourceMarkers,
+) -> Result<String, ResolveError> {
+    let mut script = String::from("ffconcat version 1.0\n");
+    let path = path.replace('\'', r"'\''");
+    for span in timespans(names, markers)? {
+        writeln!(
+            script,
+            "file '{path}'\ninpoint {}\noutpoint {}",
+            span.begin, span.end
+        )?;
+    }
+    Ok(script)
+}
+
+fn timespans(
+    names: &[impl AsRef<str>],
+    markers: &SourceMarkers,
+) -> Result<Vec<TimeSpan>, ResolveError> {
+    names
+        .iter()
+        .map(|name| find_named(&markers.timespans, name.as_ref(), |m| &m.name).map(|m| m.timespan))
+        .collect()
+}
+
+#[test]
+fn test_concat() {
+    use crate::TimespanMarker;
+    let span = |name: &str, begin, end| TimespanMarker {
+        timespan: TimeSpan { begin, end },
+        name: name.into(),
+        color: [0., 0., 0.],
+    };
+    let markers = SourceMarkers {
+        rects: Vec::new(),
+        timespans: vec![span("intro", 1.5, 3.0), span("end", 10.0, 12.25)],
+        points: Vec::new(),
+        polygons: Vec::new(),
+        colors: Vec::new(),
+    };
+    assert_eq!(
+        filter_complex(&["intro", "end"], true, &markers).unwrap(),
+        "[0:v]trim=start=1.5:end=3,setpts=PTS-STARTPTS[v0];\
+         [0:a]atrim=start=1.5:end=3,asetpts=PTS-STARTPTS[a0];\
+         [0:v]trim=start=10:end=12.25,setpts=PTS-STARTPTS[v1];\
+         [0:a]atrim=start=10:end=12.25,asetpts=PTS-STARTPTS[a1];\
+         [v0][a0][v1][a1]concat=n=2:v=1:a=1[outv][outa]"
+    );
+    assert_eq!(
+        filter_complex(&["intro"], false, &markers).unwrap(),
+        "[0:v]trim=start=1.5:end=3,setpts=PTS-STARTPTS[v0];[v0]concat=n=1:v=1:a=0[outv]"
+    );
+    assert!(filter_complex(&["nope"], true, &markers).is_err());
+    assert_eq!(
+        demuxer_script("/videos/it's.mp4", &["end", "intro"], &markers).unwrap(),
+        "ffconcat version 1.0\n\
+         file '/videos/it'\\''s.mp4'\ninpoint 10\noutpoint 12.25\n\
+         file '/videos/it'\\''s.mp4'\ninpoint 1.5\noutpoint 3\n"
+    );
+}
@@ -0,0 +1,131 @@
+//! Extra input files (audio tracks, logos, a second camera...) used in commands next to the source
+
+use {
+    super::{PATH_PARTS, ResolveError, tools::Tools},
+    crate::bg_job::BgJob,
+    std::process::Stdio,
+};
+
+pub struct Input {
+    /// Referred to as `{i.name}`, or by position as `{i.1}`, `{i.2}`...
+    pub name: String,
+    pub path: String,
+    pub streams: Vec<Stream>,
+    pub probe_err: Option<String>,
+    probe: Option<BgJob<Vec<Stream>>>,
+}
+
+pub struct Stream {
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`...
+    pub kind: String,
+    pub codec: String,
+}
+
+impl Input {
+    /// Named after the file stem, with characters that would need quoting replaced
+    pub fn new(path: String, others: &[Self], tools: &Tools) -> Self {
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        let mut name: String = stem
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        // Numeric names would be taken as a position, and path part names refer to the source
+        if name.is_empty() || name.parse::<usize>().is_ok() || PATH_PARTS.contains(&&*name) {
+            name.insert_str(0, "in");
+        }
+        while others.iter().any(|other| other.name == name) {
+            name.push('_');
+        }
+        let (probe_path, tools) = (path.clone(), tools.clone());
+        Self {
+            name,
+            path,
+            streams: Vec::new(),
+            probe_err: None,
+            probe: Some(BgJob::spawn(move || probe_streams(&tools, &probe_path))),
+        }
+    }
+    pub fn poll(&mut self) {
+        if let Some(job) = &mut self.probe
+            && let Some(result) = job.poll()
+        {
+            self.probe = None;
+            match result {
+                Ok(streams) => self.streams = streams,
+                Err(e) => self.probe_err = Some(e.to_string()),
+            }
+        }
+    }
+    pub fn is_probing(&self) -> bool {
+        self.probe.is_some()
+    }
+}
+
+/// Find an extra input by name, or by its 1-based position
+pub fn find<'a>(inputs: &'a [Input], name: &str) -> Result<(usize, &'a Input), ResolveError> {
+    let found = match name.parse::<usize>() {
+        Ok(pos) => pos
+            .checked_sub(1)
+            .and_then(|idx| Some((idx, inputs.get(idx)?))),
+        Err(_) => inputs
+            .iter()
+            .enumerate()
+            .find(|(_, input)| input.name == name),
+    };
+    found.ok_or_else(|| ResolveError::MissingItem {
+        name: format!("i.{name}"),
+    })
+}
+
+fn probe_streams(tools: &Tools, path: &str) -> anyhow::Result<Vec<Stream>> {
+    let out = tools
+        .ffprobe()
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "stream=index,codec_name,codec_type",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(parse_streams(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// Parse `index,codec_name,codec_type` lines
+fn parse_streams(csv: &str) -> Vec<Stream> {
+    csv.lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split(',');
+            Some(Stream {
+                index: fields.next()?.parse().ok()?,
+                codec: fields.next()?.to_owned(),
+                kind: fields.next()?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_streams() {
+    let streams = parse_streams("0,h264,video\n1,aac,audio\n2,,data\n");
+    let fields: Vec<_> = streams
+        .iter()
+        .map(|s| (s.index, s.kind.as_str(), s.codec.as_str()))
+        .collect();
+    assert_eq!(
+        fields,
+        [(0, "video", "h264"), (1, "audio", "aac"), (2, "data", "")]
+    );
+}
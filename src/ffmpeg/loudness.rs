@@ -0,0 +1,241 @@
+//! EBU R128 loudness measurement, using the first pass of ffmpeg's `loudnorm` filter
+
+use {
+    super::{template_output_indices, tools::Tools},
+    crate::TimeSpan,
+    std::process::Stdio,
+};
+
+/// Measured loudness of the audio
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness (LUFS)
+    pub integrated: f64,
+    /// Loudness range (LU)
+    pub lra: f64,
+    /// True peak (dBTP)
+    pub true_peak: f64,
+    pub threshold: f64,
+    pub target_offset: f64,
+    /// Sample rate of the measured audio stream
+    ///
+    /// loudnorm upsamples to 192 kHz, so the second pass has to set it back.
+    pub sample_rate: u32,
+}
+
+/// What `loudnorm` should normalize to
+#[derive(Clone, Copy, Default)]
+pub struct Target {
+    pub integrated: f64 = -16.0,
+    pub lra: f64 = 11.0,
+    pub true_peak: f64 = -1.5,
+}
+
+impl Loudness {
+    /// Measure the loudness of the first audio stream of `path`, optionally only during `range`
+    pub fn measure(
+        tools: &Tools,
+        path: &str,
+        range: Option<TimeSpan>,
+        target: Target,
+    ) -> anyhow::Result<Self> {
+        let mut cmd = tools.ffmpeg();
+        cmd.args(["-hide_banner", "-nostdin", "-nostats"]);
+        if let Some(range) = range {
+            cmd.arg("-ss")
+                .arg(range.begin.to_string())
+                .arg("-t")
+                .arg((range.end - range.begin).to_string());
+        }
+        let out = cmd
+            .arg("-i")
+            .arg(path)
+            .args(["-map", "0:a:0", "-af"])
+            .arg(format!(
+                "loudnorm=I={}:LRA={}:TP={}:print_format=json",
+                target.integrated, target.lra, target.true_peak
+            ))
+            .args(["-f", "null", "-"])
+            .stdin(Stdio::null())
+            .output()?;
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        if !out.status.success() {
+            anyhow::bail!(
+                "Loudness analysis failed: {}",
+                stderr.lines().last().unwrap_or_default().trim()
+            );
+        }
+        parse_loudnorm_json(&stderr)
+    }
+    /// The second pass, for a command with no arguments yet
+    pub fn second_pass_args(&self, target: Target, timespan: Option<&str>) -> String {
+        let ts_arg = timespan.map_or(String::new(), |name| format!(" {{t.{name}}}"));
+        format!(
+            "-i {{i}}{ts_arg} -af {} -ar {} -c:v copy out.mkv",
+            self.loudnorm_filter(target),
+            self.sample_rate
+        )
+    }
+    /// Put the second pass into the command `template`
+    ///
+    /// The filter is added to the audio filter chain of the first output, or given with `-af`
+    /// if it has none. The sample rate is set back, unless the template already sets one.
+    pub fn splice_second_pass(
+        &self,
+        target: Target,
+        template: &str,
+    ) -> Result<String, shell_words::ParseError> {
+        let mut words = shell_words::split(template)?;
+        let end = template_output_indices(&words)
+            .first()
+            .map_or(words.len(), |&idx| idx);
+        let is_af = |arg: &String| ["-af", "-filter:a", "-filter:a:0"].contains(&arg.as_str());
+        let mut inserted = Vec::new();
+        match words[..end].iter().rposition(is_af) {
+            Some(idx) if idx + 1 < end => {
+                words[idx + 1] = format!("{},{}", words[idx + 1], self.loudnorm_filter(target));
+            }
+            _ => inserted.extend(["-af".into(), self.loudnorm_filter(target)]),
+        }
+        if !words[..end]
+            .iter()
+            .any(|arg| arg == "-ar" || arg.starts_with("-ar:"))
+        {
+            inserted.extend(["-ar".into(), self.sample_rate.to_string()]);
+        }
+        words.splice(end..end, inserted);
+        Ok(words
+            .iter()
+            .map(|word| {
+                // Keep `{i}` and such readable, shell_words would quote them
+                if word.is_empty()
+                    || word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c))
+                {
+                    shell_words::quote(word)
+                } else {
+                    word.into()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+    /// `loudnorm` filter for the second pass, using these measurements
+    pub fn loudnorm_filter(&self, target: Target) -> String {
+        format!(
+            "loudnorm=I={}:LRA={}:TP={}:measured_I={}:measured_LRA={}:measured_TP={}:\
+             measured_thresh={}:offset={}:linear=true",
+            target.integrated,
+            target.lra,
+            target.true_peak,
+            self.integrated,
+            self.lra,
+            self.true_peak,
+            self.threshold,
+            self.target_offset,
+        )
+    }
+}
+
+/// Parse the json `loudnorm` prints at the end of its output
+///
+/// The json is flat and only has string values, so it's simple enough to do by hand.
+fn parse_loudnorm_json(output: &str) -> anyhow::Result<Loudness> {
+    let Some(json_begin) = output.rfind('{') else {
+        anyhow::bail!("No loudnorm output found");
+    };
+    let field = |name: &str| -> anyhow::Result<f64> {
+        output[json_begin..]
+            .lines()
+            .find_map(|line| {
+                let (key, val) = line.split_once(':')?;
+                (key.trim().trim_matches('"') == name)
+                    .then(|| val.trim().trim_end_matches(',').trim_matches('"'))
+            })
+            .ok_or_else(|| anyhow::anyhow!("Missing {name} in loudnorm output"))?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid {name} in loudnorm output: {e}"))
+    };
+    Ok(Loudness {
+        integrated: field("input_i")?,
+        lra: field("input_lra")?,
+        true_peak: field("input_tp")?,
+        threshold: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+        sample_rate: parse_sample_rate(output).unwrap_or(48_000),
+    })
+}
+
+/// Sample rate of the first audio stream, from the input listing of ffmpeg's output
+fn parse_sample_rate(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .take_while(|line| !line.starts_with("Output #"))
+        .find(|line| line.contains(": Audio: "))?
+        .split(", ")
+        .find_map(|field| field.strip_suffix(" Hz")?.parse().ok())
+}
+
+#[test]
+fn test_parse_loudnorm_json() {
+    let output = r#"
+Input #0, matroska,webm, from 'in.mkv':
+  Stream #0:0: Video: h264 (High), yuv420p(progressive), 1920x1080, 25 fps
+  Stream #0:1(eng): Audio: opus, 44100 Hz, stereo, fltp (default)
+Stream mapping:
+  Stream #0:1 -> #0:0 (opus (native) -> pcm_s16le (native))
+Output #0, null, to 'pipe:':
+  Stream #0:0(eng): Audio: pcm_s16le, 192000 Hz, stereo, s16, 6144 kb/s (default)
+[Parsed_loudnorm_0 @ 0x5581c0f0]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+"#;
+    let loudness = parse_loudnorm_json(output).unwrap();
+    assert_eq!(
+        loudness,
+        Loudness {
+            integrated: -27.61,
+            lra: 18.06,
+            true_peak: -4.47,
+            threshold: -39.2,
+            target_offset: 0.58,
+            sample_rate: 44100,
+        }
+    );
+    assert!(parse_loudnorm_json("Error opening input").is_err());
+}
+
+#[test]
+fn test_splice_second_pass() {
+    let loudness = Loudness {
+        integrated: -20.0,
+        lra: 5.0,
+        true_peak: -3.0,
+        threshold: -30.0,
+        target_offset: 0.5,
+        sample_rate: 48000,
+    };
+    let target = Target::default();
+    let filter = loudness.loudnorm_filter(target);
+    let splice = |template| loudness.splice_second_pass(target, template).unwrap();
+    assert_eq!(
+        splice("-i {i} {t.a} -af 'volume=2, highpass=f=80' {v.x} {out:_norm.mkv}"),
+        format!(
+            "-i {{i}} {{t.a}} -af 'volume=2, highpass=f=80,{filter}' {{v.x}} -ar 48000 \
+             {{out:_norm.mkv}}"
+        )
+    );
+    assert_eq!(
+        splice("-i {i} -ar 44100 out.wav"),
+        format!("-i {{i}} -ar 44100 -af {filter} out.wav")
+    );
+}
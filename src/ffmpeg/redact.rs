@@ -0,0 +1,156 @@
+//! Generating filter graphs that blur or pixelate the area of rect markers
+
+use {
+    super::{ResolveError, find_named, rect_exprs},
+    crate::{RectMarker, SourceMarkers},
+    std::fmt::Write as _,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    #[default]
+    Blur,
+    Pixelate,
+}
+
+impl Style {
+    pub const ALL: [Self; 2] = [Self::Blur, Self::Pixelate];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blur => "Blur",
+            Self::Pixelate => "Pixelate",
+        }
+    }
+}
+
+/// A rect marker to redact, only during a timespan marker if there is one
+pub struct Region {
+    pub rect: String,
+    pub timespan: Option<String>,
+}
+
+/// A `-filter_complex` graph that redacts `regions` of `[0:v]`, and outputs `[out]`
+///
+/// `strength` is the blur radius, or the size of the pixelation blocks.
+pub fn filter_complex(
+    regions: &[Region],
+    style: Style,
+    strength: u16,
+    markers: &SourceMarkers,
+) -> Result<String, ResolveError> {
+    if regions.is_empty() {
+        return Ok("[0:v]null[out]".into());
+    }
+    let strength = strength.max(1);
+    let mut graph = format!("[0:v]split={}[v]", regions.len() + 1);
+    for i in 0..regions.len() {
+        write!(graph, "[c{i}]")?;
+    }
+    for (i, region) in regions.iter().enumerate() {
+        let marker = find_named(&markers.rects, &region.rect, |m| &m.name)?;
+        let [w, h, x, y] = rect_exprs(marker);
+        write!(graph, ";[c{i}]crop={w}:{h}:{x}:{y},")?;
+        match style {
+            // The radius can't be larger than half of the (chroma) plane
+            Style::Blur => write!(
+                graph,
+                "boxblur=luma_radius='min({strength},min(w,h)/2)':\
+                 chroma_radius='min({strength},min(cw,ch)/2)':luma_power=2"
+            )?,
+            Style::Pixelate => write!(
+                graph,
+                "scale='max(1,iw/{strength})':'max(1,ih/{strength})':flags=area,\
+                 scale={w}:{h}:flags=neighbor"
+            )?,
+        }
+        write!(graph, "[r{i}]")?;
+        let prev = if i == 0 {
+            "v".to_owned()
+        } else {
+            format!("v{}", i - 1)
+        };
+        write!(graph, ";[{prev}][r{i}]overlay=x={x}:y={y}")?;
+        if let Some(name) = &region.timespan {
+            let marker = find_named(&markers.timespans, name, |m| &m.name)?;
+            write!(
+                graph,
+                ":enable='between(t,{},{})'",
+                marker.timespan.begin, marker.timespan.end
+            )?;
+        }
+        if i + 1 == regions.len() {
+            graph.push_str("[out]");
+        } else {
+            write!(graph, "[v{i}]")?;
+        }
+    }
+    Ok(graph)
+}
+
+#[test]
+fn test_filter_complex() {
+    use crate::{
+        TimeSpan, TimespanMarker,
+        coords::{VideoDim, VideoPos, VideoRect},
+    };
+    let rect = |name: &str, x| RectMarker {
+        rect: VideoRect {
+            pos: VideoPos::new(x, 20),
+            dim: VideoDim::new(100, 50),
+        },
+        name: name.into(),
+        color: [0., 0., 0.],
+        constraints: Default::default(),
+        keyframes: Vec::new(),
+    };
+    let markers = SourceMarkers {
+        rects: vec![rect("face", 10), rect("plate", 200)],
+        timespans: vec![TimespanMarker {
+            timespan: TimeSpan {
+                begin: 1.5,
+                end: 4.0,
+            },
+            name: "intro".into(),
+            color: [0., 0., 0.],
+        }],
+        points: Vec::new(),
+        polygons: Vec::new(),
+        colors: Vec::new(),
+    };
+    let regions = [
+        Region {
+            rect: "face".into(),
+            timespan: Some("intro".into()),
+        },
+        Region {
+            rect: "plate".into(),
+            timespan: None,
+        },
+    ];
+    assert_eq!(
+        filter_complex(&regions, Style::Pixelate, 10, &markers).unwrap(),
+        "[0:v]split=3[v][c0][c1];\
+         [c0]crop=100:50:10:20,scale='max(1,iw/10)':'max(1,ih/10)':flags=area,\
+         scale=100:50:flags=neighbor[r0];\
+         [v][r0]overlay=x=10:y=20:enable='between(t,1.5,4)'[v0];\
+         [c1]crop=100:50:200:20,scale='max(1,iw/10)':'max(1,ih/10)':flags=area,\
+         scale=100:50:flags=neighbor[r1];\
+         [v0][r1]overlay=x=200:y=20[out]"
+    );
+    assert_eq!(
+        filter_complex(&[], Style::Blur, 10, &markers).unwrap(),
+        "[0:v]null[out]"
+    );
+    assert!(
+        filter_complex(
+            &[Region {
+                rect: "nope".into(),
+                timespan: None
+            }],
+            Style::Blur,
+            10,
+            &markers
+        )
+        .is_err()
+    );
+}
@@ -0,0 +1,213 @@
+//! Which ffmpeg and ffprobe to run, and how
+
+use {
+    serde::{Deserialize, Serialize},
+    std::{
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+    },
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tools {
+    /// Path of the ffmpeg binary, or a name looked up in `PATH`
+    pub ffmpeg: String,
+    /// Path of the ffprobe binary, or a name looked up in `PATH`
+    pub ffprobe: String,
+    /// Arguments put before the ones of commands run from the ffmpeg window, like `-loglevel warning`
+    ///
+    /// Analysis commands (waveform, scene detection...) parse ffmpeg's log, so they don't get them.
+    pub global_args: String,
+    /// Extra environment variables for every ffmpeg and ffprobe command
+    pub env: Vec<(String, String)>,
+    /// Where commands of the ffmpeg window run, so relative outputs end up there.
+    /// Empty for the current directory.
+    pub working_dir: String,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Self {
+            ffmpeg: "ffmpeg".into(),
+            ffprobe: "ffprobe".into(),
+            global_args: String::new(),
+            env: Vec::new(),
+            working_dir: String::new(),
+        }
+    }
+}
+
+impl Tools {
+    /// ffmpeg, with the configured environment
+    pub fn ffmpeg(&self) -> Command {
+        self.command(&self.ffmpeg)
+    }
+    /// ffprobe, with the configured environment
+    pub fn ffprobe(&self) -> Command {
+        self.command(&self.ffprobe)
+    }
+    /// ffmpeg for running the command of the ffmpeg window: also with the global arguments,
+    /// in the working directory
+    pub fn user_ffmpeg(&self) -> anyhow::Result<Command> {
+        let mut cmd = self.command(&self.ffmpeg);
+        cmd.args(shell_words::split(&self.global_args)?);
+        if !self.working_dir.is_empty() {
+            cmd.current_dir(&self.working_dir);
+        }
+        Ok(cmd)
+    }
+    /// Where a path given to a command of the ffmpeg window points to
+    pub fn in_working_dir(&self, path: &str) -> PathBuf {
+        if self.working_dir.is_empty() {
+            PathBuf::from(path)
+        } else {
+            Path::new(&self.working_dir).join(path)
+        }
+    }
+    fn command(&self, program: &str) -> Command {
+        let mut cmd = Command::new(program);
+        cmd.envs(
+            self.env
+                .iter()
+                .filter(|(name, _)| !name.is_empty())
+                .map(|(name, value)| (name, value)),
+        );
+        cmd
+    }
+}
+
+/// What the configured ffmpeg can do
+pub struct Detection {
+    /// First line of `ffmpeg -version`
+    pub ffmpeg_version: String,
+    /// First line of `ffprobe -version`
+    pub ffprobe_version: String,
+    /// Options ffmpeg was configured with when it was built
+    pub configuration: Vec<String>,
+    pub encoders: Vec<Entry>,
+    pub filters: Vec<Entry>,
+}
+
+/// An encoder or a filter
+pub struct Entry {
+    pub name: String,
+    pub desc: String,
+}
+
+/// Run the configured binaries to find out their version, build configuration, encoders and filters
+pub fn detect(tools: &Tools) -> anyhow::Result<Detection> {
+    let version = output(tools.ffmpeg().arg("-version"), "ffmpeg")?;
+    let probe_version = output(tools.ffprobe().arg("-version"), "ffprobe")?;
+    let encoders = output(tools.ffmpeg().args(["-hide_banner", "-encoders"]), "ffmpeg")?;
+    let filters = output(tools.ffmpeg().args(["-hide_banner", "-filters"]), "ffmpeg")?;
+    Ok(Detection {
+        ffmpeg_version: version.lines().next().unwrap_or_default().to_owned(),
+        ffprobe_version: probe_version.lines().next().unwrap_or_default().to_owned(),
+        configuration: parse_configuration(&version),
+        encoders: parse_list(&encoders, 0),
+        // Filters have an `A->V` style column between the name and the description
+        filters: parse_list(&filters, 1),
+    })
+}
+
+fn output(cmd: &mut Command, name: &str) -> anyhow::Result<String> {
+    let out = match cmd.stdin(Stdio::null()).output() {
+        Ok(out) => out,
+        Err(e) => anyhow::bail!("Couldn't run {name}: {e}"),
+    };
+    if !out.status.success() {
+        anyhow::bail!(
+            "{name} failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// The options of the `configuration:` line of `ffmpeg -version`
+fn parse_configuration(version: &str) -> Vec<String> {
+    version
+        .lines()
+        .find_map(|line| line.strip_prefix("configuration:"))
+        .map_or(Vec::new(), |opts| {
+            opts.split_whitespace().map(str::to_owned).collect()
+        })
+}
+
+/// Parse the `-encoders` or `-filters` listing, skipping the legend
+///
+/// Entries are `flags name [skip columns] description`.
+fn parse_list(text: &str, skip: usize) -> Vec<Entry> {
+    text.lines()
+        .filter_map(|line| {
+            let mut rest = line.trim();
+            let mut fields = Vec::new();
+            for _ in 0..2 + skip {
+                let (field, tail) = rest.split_once(char::is_whitespace)?;
+                fields.push(field);
+                rest = tail.trim_start();
+            }
+            // Legend lines are like `V..... = Video`
+            if fields[1] == "=" {
+                return None;
+            }
+            Some(Entry {
+                name: fields[1].to_owned(),
+                desc: rest.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_detection() {
+    let version = "\
+ffmpeg version 7.1 Copyright (c) 2000-2024 the FFmpeg developers
+built with gcc 14.2.1 (GCC) 20240910
+configuration: --prefix=/usr --enable-gpl --enable-libx264
+libavutil      59. 39.100 / 59. 39.100
+";
+    assert_eq!(
+        parse_configuration(version),
+        ["--prefix=/usr", "--enable-gpl", "--enable-libx264"]
+    );
+    let encoders = "\
+Encoders:
+ V..... = Video
+ A..... = Audio
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+";
+    let entries = |list: Vec<Entry>| -> Vec<(String, String)> {
+        list.into_iter().map(|e| (e.name, e.desc)).collect()
+    };
+    assert_eq!(
+        entries(parse_list(encoders, 0)),
+        [
+            (
+                "libx264".into(),
+                "libx264 H.264 / AVC / MPEG-4 AVC (codec h264)".into()
+            ),
+            ("aac".into(), "AAC (Advanced Audio Coding)".into()),
+        ]
+    );
+    let filters = "\
+Filters:
+  T.. = Timeline support
+  | = Source or sink filter
+ TSC acompressor       A->A       Audio compressor.
+ ... scale             V->V       Scale the input video size and/or convert the image format.
+";
+    assert_eq!(
+        entries(parse_list(filters, 1)),
+        [
+            ("acompressor".into(), "Audio compressor.".into()),
+            (
+                "scale".into(),
+                "Scale the input video size and/or convert the image format.".into()
+            ),
+        ]
+    );
+}
@@ -7,23 +7,31 @@ use {
     app::App,
     clap::Parser,
     config::Config,
-    coords::{Src, VideoPos, VideoRect},
+    coords::{Src, VideoMag, VideoPos, VideoRect},
     egui_sf2g::sf2g::graphics::Font,
     mpv::{
         commands::LoadFile,
         properties::{AudioPitchCorrection, CropX, Height, KeepOpen, KeepOpenPause, Volume, Width},
         property::{YesNo, YesNoAlways},
     },
+    rect_constraints::RectConstraints,
+    rect_keyframes::RectKeyframe,
     ui::EguiFriendlyColor,
 };
 
 mod app;
+mod bg_job;
+mod color;
+mod compare;
 mod config;
 mod coords;
 mod ffmpeg;
 mod mpv;
 mod overlay;
+mod playlist;
 mod present;
+mod rect_constraints;
+mod rect_keyframes;
 mod sfml_integ;
 mod source;
 mod subs;
@@ -40,6 +48,9 @@ struct RectMarker {
     rect: VideoRect<Src>,
     name: String,
     color: EguiFriendlyColor,
+    constraints: RectConstraints,
+    /// If not empty, the rect is animated between these (sorted by time)
+    keyframes: Vec<RectKeyframe>,
 }
 
 struct TimespanMarker {
@@ -48,10 +59,46 @@ struct TimespanMarker {
     color: EguiFriendlyColor,
 }
 
+/// A single named position, e.g. for `overlay=x:y` or `drawtext` anchors
+struct PointMarker {
+    pos: VideoPos<Src>,
+    name: String,
+    color: EguiFriendlyColor,
+}
+
+/// A closed polygon, e.g. for generating masks
+struct PolygonMarker {
+    points: Vec<VideoPos<Src>>,
+    name: String,
+    color: EguiFriendlyColor,
+}
+
+/// A colour picked from the video, e.g. for `colorkey` or `drawtext` `fontcolor`
+struct ColorMarker {
+    rgb: color::Rgb,
+    name: String,
+}
+
 #[derive(Default)]
 struct SourceMarkers {
     rects: Vec<RectMarker>,
     timespans: Vec<TimespanMarker>,
+    points: Vec<PointMarker>,
+    polygons: Vec<PolygonMarker>,
+    colors: Vec<ColorMarker>,
+}
+
+impl SourceMarkers {
+    /// Move animated rects to where they are at `time`, except the one being dragged
+    fn follow_rect_keyframes(&mut self, time: f64, dragged: Option<usize>) {
+        for (i, marker) in self.rects.iter_mut().enumerate() {
+            if Some(i) != dragged
+                && let Some(rect) = rect_keyframes::rect_at(&marker.keyframes, time)
+            {
+                marker.rect = rect;
+            }
+        }
+    }
 }
 
 impl RectDrag {
@@ -66,22 +113,133 @@ impl RectDrag {
 enum RectDragStatus {
     Init,
     ClickedTopLeft,
+    /// Moving or resizing an existing rect by one of its handles
+    Manipulate {
+        handle: RectHandle,
+        /// Mouse position when the drag started
+        origin: VideoPos<Src>,
+        /// The rect as it was when the drag started
+        orig_rect: VideoRect<Src>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum RectHandle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+    /// Anywhere inside the rect, moves the whole rect
+    Body,
+}
+
+impl RectHandle {
+    const EDGES: [Self; 8] = [
+        Self::TopLeft,
+        Self::Top,
+        Self::TopRight,
+        Self::Right,
+        Self::BottomRight,
+        Self::Bottom,
+        Self::BottomLeft,
+        Self::Left,
+    ];
+    /// Where the handle sits on the rect, per axis: 0 = start, 1 = middle, 2 = end
+    fn anchor(self) -> (u8, u8) {
+        match self {
+            Self::TopLeft => (0, 0),
+            Self::Top => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::Right => (2, 1),
+            Self::BottomRight => (2, 2),
+            Self::Bottom => (1, 2),
+            Self::BottomLeft => (0, 2),
+            Self::Left => (0, 1),
+            Self::Body => (1, 1),
+        }
+    }
+    /// Apply a mouse movement of (`dx`, `dy`) to `orig` by dragging this handle
+    ///
+    /// Dragging an edge past the opposite one flips the rect instead of making it negative.
+    fn apply(self, orig: VideoRect<Src>, dx: VideoMag, dy: VideoMag) -> VideoRect<Src> {
+        if let Self::Body = self {
+            return VideoRect::new(orig.pos.x + dx, orig.pos.y + dy, orig.dim.x, orig.dim.y);
+        }
+        let (mut start, mut end) = (orig.pos, orig.end());
+        match self.anchor() {
+            (0, _) => start.x += dx,
+            (2, _) => end.x += dx,
+            _ => {}
+        }
+        match self.anchor() {
+            (_, 0) => start.y += dy,
+            (_, 2) => end.y += dy,
+            _ => {}
+        }
+        VideoRect::from_corners(start, end)
+    }
+}
+
+/// Dragging a timespan marker on the timeline
+struct TimespanDrag {
+    idx: usize,
+    kind: TimespanDragKind,
+    /// Time under the mouse when the drag started
+    origin: f64,
+    /// The timespan as it was when the drag started
+    orig_span: TimeSpan,
+    /// Whether the mouse moved since the drag started
+    moved: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimespanDragKind {
+    Begin,
+    End,
+    Move,
+    /// Right-dragging a new timespan into existence
+    Create,
+}
+
+/// Picking positions with the mouse in the video view
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pick {
+    /// Set the position of a point marker
+    Point(usize),
+    /// Append vertices to a polygon marker, until picking is stopped
+    PolygonVertices(usize),
+    /// Set a colour marker to the colour of the clicked pixel
+    Color(usize),
 }
 
 struct InteractState {
+    pick: Option<Pick>,
     rect_drag: Option<RectDrag>,
+    /// Rect that was last clicked in the video view. Arrow keys nudge it instead of seeking.
+    active_rect: Option<usize>,
+    timespan_drag: Option<TimespanDrag>,
     pan_cursor_origin: Option<VideoPos<Src>>,
     pan_image_original_pos: Option<VideoPos<Src>>,
     pan_pos: VideoPos<Src>,
+    /// How many times the waveform lane is zoomed in, relative to the whole duration
+    waveform_zoom: f64,
 }
 
 impl Default for InteractState {
     fn default() -> Self {
         Self {
+            pick: Default::default(),
             rect_drag: Default::default(),
+            active_rect: Default::default(),
+            timespan_drag: Default::default(),
             pan_cursor_origin: Default::default(),
             pan_image_original_pos: Default::default(),
             pan_pos: VideoPos::new(0, 0),
+            waveform_zoom: 1.0,
         }
     }
 }
@@ -101,6 +259,8 @@ impl TimeSpan {
 enum TabOpen {
     Rects,
     Timespans,
+    Points,
+    Colors,
 }
 
 #[derive(clap::Parser)]
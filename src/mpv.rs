@@ -133,6 +133,19 @@ impl Mpv {
         }
     }
 
+    /// RGB of the last rendered frame at a present space position
+    pub fn pixel_at(&self, present_dim: VideoDim<Present>, x: i32, y: i32) -> Option<[u8; 3]> {
+        let (w, h) = (i32::from(present_dim.x), i32::from(present_dim.y));
+        if !(0..w).contains(&x) || !(0..h).contains(&y) {
+            return None;
+        }
+        let idx = (y as usize * w as usize + x as usize) * 4;
+        let &[r, g, b, _] = self.pix_buf.get(idx..idx + 4)? else {
+            return None;
+        };
+        Some([r, g, b])
+    }
+
     /// See [`properties`] for the properties you can use.
     pub fn get_property<P: Property>(&self) -> Option<P::Type> {
         let mut out: MaybeUninit<<P::Type as PropertyType>::CType> = MaybeUninit::uninit();
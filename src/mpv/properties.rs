@@ -24,6 +24,7 @@ def_properties! {
     Volume, c"volume", f64;
     Duration, c"duration", f64;
     Pause, c"pause", bool;
+    Mute, c"mute", bool;
     AudioPitchCorrection, c"audio-pitch-correction", bool;
     KeepOpen, c"keep-open", YesNoAlways;
     KeepOpenPause, c"keep-open-pause", YesNo;
@@ -38,6 +39,10 @@ def_properties! {
     Rotate, c"video-params/rotate", i64;
     AudioId, c"aid", i64;
     SubId, c"sid", i64;
+    /// Frame number of the current frame, estimated from the time position and fps
+    EstimatedFrameNumber, c"estimated-frame-number", i64;
+    EstimatedFrameCount, c"estimated-frame-count", i64;
+    ContainerFps, c"container-fps", f64;
 }
 
 pub struct Path<'mpv> {
@@ -49,10 +54,41 @@ unsafe impl<'mpv> Property for Path<'mpv> {
     const NAME: &'static CStr = c"path";
 }
 
+/// Picture type (I/P/B) of the current video frame
+pub struct PictureType<'mpv> {
+    _phantom: PhantomData<&'mpv str>,
+}
+unsafe impl<'mpv> Property for PictureType<'mpv> {
+    type Type = &'mpv str;
+
+    const NAME: &'static CStr = c"video-frame-info/picture-type";
+}
+
+/// Video filter chain that mpv applies during playback
+pub struct Vf<'a> {
+    _phantom: PhantomData<&'a str>,
+}
+unsafe impl<'a> Property for Vf<'a> {
+    type Type = &'a str;
+
+    const NAME: &'static CStr = c"vf";
+}
+
+/// Audio filter chain that mpv applies during playback
+pub struct Af<'a> {
+    _phantom: PhantomData<&'a str>,
+}
+unsafe impl<'a> Property for Af<'a> {
+    type Type = &'a str;
+
+    const NAME: &'static CStr = c"af";
+}
+
 unsafe impl PropertyWrite for TimePos {}
 unsafe impl PropertyWrite for Speed {}
 unsafe impl PropertyWrite for Volume {}
 unsafe impl PropertyWrite for Pause {}
+unsafe impl PropertyWrite for Mute {}
 unsafe impl PropertyWrite for AudioPitchCorrection {}
 unsafe impl PropertyWrite for KeepOpen {}
 unsafe impl PropertyWrite for KeepOpenPause {}
@@ -64,6 +100,18 @@ unsafe impl PropertyUnset for AbLoopA {
 
     const UNSET_VALUE: <Self as PropertyUnset>::UnsetType = "no";
 }
+unsafe impl PropertyWrite for Vf<'_> {}
+unsafe impl PropertyUnset for Vf<'_> {
+    type UnsetType = &'static str;
+
+    const UNSET_VALUE: <Self as PropertyUnset>::UnsetType = "";
+}
+unsafe impl PropertyWrite for Af<'_> {}
+unsafe impl PropertyUnset for Af<'_> {
+    type UnsetType = &'static str;
+
+    const UNSET_VALUE: <Self as PropertyUnset>::UnsetType = "";
+}
 unsafe impl PropertyWrite for AbLoopB {}
 unsafe impl PropertyUnset for AbLoopB {
     type UnsetType = &'static str;
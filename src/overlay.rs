@@ -1,15 +1,17 @@
 use {
     crate::{
+        RectHandle, RectMarker, TimeSpan, TimespanDrag, TimespanDragKind, TimespanMarker,
         app::AppState,
-        coords::{Dim, Present, VideoDim, VideoMag, VideoVector},
+        color,
+        coords::{Dim, Present, Src, VideoDim, VideoMag, VideoPos, VideoVector},
         mpv::{Mpv, properties::TimePos},
         sfml_integ::{EguiFriendlyColorExt as _, VideoPosSfExt},
-        source,
+        source::{self, thumbnails::THUMB_H},
         time_fmt::FfmpegTimeFmt,
     },
     egui_sf2g::sf2g::{
         graphics::{
-            Color, Font, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Text,
+            Color, Font, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Sprite, Text,
             Transformable,
         },
         system::Vector2,
@@ -20,31 +22,228 @@ use {
 
 const TIMELINE_MARGIN: VideoMag = 20;
 const TIMELINE_H: VideoMag = 12;
+const FILMSTRIP_H: f32 = 32.0;
+const WAVEFORM_H: VideoMag = 48;
+/// Space between the timeline and the lanes below it, leaving room for the keyframe ticks
+const LANES_GAP: VideoMag = 8;
+/// Height of a silence/black detection lane
+const GAP_LANE_H: VideoMag = 6;
+const GAP_LANE_SPACING: VideoMag = 2;
+
+/// How close (in pixels) the mouse has to be to a timespan edge to grab it
+const HANDLE_GRAB_DIST: VideoMag = 4;
+/// Size of the squares drawn at the edges and corners of the active rect marker
+const RECT_HANDLE_SIZE: f32 = 8.0;
+
+/// Half the length of the crosshair arms of point markers
+const POINT_CROSS_SIZE: f32 = 6.0;
+const POLYGON_VERTEX_SIZE: f32 = 6.0;
 
 type VideoRect = Rect<VideoMag>;
 
-pub fn handle_event(
-    event: &Event,
-    mpv: &Mpv,
-    src_info: &source::Info,
-    video_area_max_dim: VideoDim<Present>,
-) {
-    if let Event::MouseButtonPressed {
-        button: mouse::Button::Left,
-        x,
-        y,
-    } = *event
-    {
-        let x = x as VideoMag;
-        let y = y as VideoMag;
-        let timeline_rect = timeline_rect(video_area_max_dim);
-        if timeline_rect.contains((x, y).into()) {
-            let time_pos = timeline_rect_timepos(timeline_rect, x, src_info);
-            mpv.set_property::<TimePos>(time_pos);
+pub fn handle_event(event: &Event, mpv: &Mpv, app_state: &mut AppState) {
+    let src_info = &app_state.src;
+    let interact = &mut app_state.interact;
+    let markers = &mut app_state.source_markers.timespans;
+    let timeline_rect = timeline_rect(app_state.video_area_max_dim, src_info);
+    match *event {
+        Event::MouseButtonPressed {
+            button: mouse::Button::Left,
+            x,
+            y,
+        } => {
+            let x = x as VideoMag;
+            let y = y as VideoMag;
+            if timeline_rect.contains((x, y).into()) {
+                let time_pos = timeline_rect_timepos(timeline_rect, x, src_info);
+                match grab_timespan(timeline_rect, x, markers, src_info.duration) {
+                    Some((idx, kind)) => {
+                        interact.timespan_drag = Some(TimespanDrag {
+                            idx,
+                            kind,
+                            origin: time_pos,
+                            orig_span: markers[idx].timespan,
+                            moved: false,
+                        });
+                    }
+                    None => {
+                        mpv.set_property::<TimePos>(time_pos);
+                    }
+                }
+            }
+            if let Some(waveform_rect) = waveform_rect(app_state.video_area_max_dim, src_info)
+                && waveform_rect.contains((x, y).into())
+            {
+                let (begin, end) = waveform_window(src_info, interact.waveform_zoom);
+                let ratio = (x - waveform_rect.left) as f64 / waveform_rect.width as f64;
+                mpv.set_property::<TimePos>(begin + ratio * (end - begin));
+            }
+            if let Some(lanes_rect) = gap_lanes_rect(app_state.video_area_max_dim, src_info)
+                && lanes_rect.contains((x, y).into())
+            {
+                mpv.set_property::<TimePos>(timeline_rect_timepos(lanes_rect, x, src_info));
+            }
+        }
+        Event::MouseButtonPressed {
+            button: mouse::Button::Right,
+            x,
+            y,
+        } => {
+            let x = x as VideoMag;
+            if timeline_rect.contains((x, y as VideoMag).into()) {
+                let time_pos =
+                    src_info.snap_to_frame(timeline_rect_timepos(timeline_rect, x, src_info));
+                let span = TimeSpan {
+                    begin: time_pos,
+                    end: time_pos,
+                };
+                crate::ui::push_numbered_timespans(markers, "", vec![span]);
+                interact.timespan_drag = Some(TimespanDrag {
+                    idx: markers.len() - 1,
+                    kind: TimespanDragKind::Create,
+                    origin: time_pos,
+                    orig_span: span,
+                    moved: false,
+                });
+            }
+        }
+        Event::MouseMoved { x, .. } => {
+            if let Some(drag) = &mut interact.timespan_drag
+                && let Some(marker) = markers.get_mut(drag.idx)
+            {
+                drag.moved = true;
+                let time = timeline_rect_timepos(timeline_rect, x as VideoMag, src_info)
+                    .clamp(0.0, src_info.duration);
+                let time = src_info.snap_to_frame(time);
+                let span = &mut marker.timespan;
+                match drag.kind {
+                    TimespanDragKind::Begin => span.begin = time.min(span.end),
+                    TimespanDragKind::End => span.end = time.max(span.begin),
+                    TimespanDragKind::Move => {
+                        let len = drag.orig_span.end - drag.orig_span.begin;
+                        let begin = (drag.orig_span.begin + time - drag.origin)
+                            .clamp(0.0, (src_info.duration - len).max(0.0));
+                        span.begin = src_info.snap_to_frame(begin);
+                        span.end = span.begin + len;
+                    }
+                    TimespanDragKind::Create => {
+                        span.begin = time.min(drag.origin);
+                        span.end = time.max(drag.origin);
+                    }
+                }
+            }
+        }
+        Event::MouseButtonReleased { .. } => {
+            if let Some(drag) = interact.timespan_drag.take() {
+                match drag.kind {
+                    // Clicking a timespan without dragging it should still seek
+                    TimespanDragKind::Move if !drag.moved => {
+                        mpv.set_property::<TimePos>(drag.origin);
+                    }
+                    // Right click without dragging, don't leave an empty timespan around
+                    TimespanDragKind::Create
+                        if markers
+                            .get(drag.idx)
+                            .is_some_and(|m| m.timespan.begin == m.timespan.end) =>
+                    {
+                        markers.remove(drag.idx);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Event::MouseWheelScrolled { delta, x, y, .. } => {
+            if let Some(waveform_rect) = waveform_rect(app_state.video_area_max_dim, src_info)
+                && waveform_rect.contains((x as VideoMag, y as VideoMag).into())
+            {
+                let factor = if delta > 0.0 { 1.25 } else { 0.8 };
+                interact.waveform_zoom = (interact.waveform_zoom * factor).clamp(1.0, 1000.0);
+            }
         }
+        _ => {}
     }
 }
 
+/// Find the timespan (and which part of it) under the mouse at `x` on the timeline
+///
+/// Edges take priority over the body, and later timespans over earlier ones.
+fn grab_timespan(
+    timeline_rect: VideoRect,
+    x: VideoMag,
+    markers: &[TimespanMarker],
+    duration: f64,
+) -> Option<(usize, TimespanDragKind)> {
+    let time_to_x =
+        |time: f64| timeline_rect.left as f64 + time / duration * timeline_rect.width as f64;
+    let near = |time: f64| (time_to_x(time) - x as f64).abs() <= HANDLE_GRAB_DIST as f64;
+    let rev_iter = || markers.iter().enumerate().rev();
+    rev_iter()
+        .find_map(|(i, marker)| {
+            if near(marker.timespan.end) {
+                Some((i, TimespanDragKind::End))
+            } else if near(marker.timespan.begin) {
+                Some((i, TimespanDragKind::Begin))
+            } else {
+                None
+            }
+        })
+        .or_else(|| {
+            rev_iter().find_map(|(i, marker)| {
+                let x = x as f64;
+                (time_to_x(marker.timespan.begin)..=time_to_x(marker.timespan.end))
+                    .contains(&x)
+                    .then_some((i, TimespanDragKind::Move))
+            })
+        })
+}
+
+/// Whether a (present space) position is over the timeline or one of the lanes below it
+pub fn is_over_timeline(app_state: &AppState, x: i32, y: i32) -> bool {
+    let pos = (x as VideoMag, y as VideoMag).into();
+    timeline_rect(app_state.video_area_max_dim, &app_state.src).contains(pos)
+        || gap_lanes_rect(app_state.video_area_max_dim, &app_state.src)
+            .is_some_and(|rect| rect.contains(pos))
+        || waveform_rect(app_state.video_area_max_dim, &app_state.src)
+            .is_some_and(|rect| rect.contains(pos))
+}
+
+/// Rect marker in present space, as `(left, top, width, height)`
+fn rect_marker_present(marker: &RectMarker, app_state: &AppState) -> [f32; 4] {
+    let present_dim = app_state
+        .present
+        .as_ref()
+        .map_or(VideoVector::new(0, 0), |present| present.dim);
+    let pos = marker.rect.pos.to_present(app_state.src.dim, present_dim);
+    let dim = marker.rect.dim.to_present(app_state.src.dim, present_dim);
+    [pos.x.into(), pos.y.into(), dim.x.into(), dim.y.into()]
+}
+
+/// Center of `handle` on a present space rect
+fn rect_handle_pos(handle: RectHandle, [x, y, w, h]: [f32; 4]) -> (f32, f32) {
+    let (ax, ay) = handle.anchor();
+    (x + w * f32::from(ax) / 2.0, y + h * f32::from(ay) / 2.0)
+}
+
+/// Find the rect marker (and which handle of it) under a present space position
+///
+/// Later rects are drawn on top, so they take priority.
+pub fn grab_rect(app_state: &AppState, x: i32, y: i32) -> Option<(usize, RectHandle)> {
+    let (x, y) = (x as f32, y as f32);
+    let markers = &app_state.source_markers.rects;
+    markers.iter().enumerate().rev().find_map(|(i, marker)| {
+        let rect = rect_marker_present(marker, app_state);
+        let on_handle = RectHandle::EDGES.into_iter().find(|&handle| {
+            let (hx, hy) = rect_handle_pos(handle, rect);
+            (hx - x).abs() <= RECT_HANDLE_SIZE && (hy - y).abs() <= RECT_HANDLE_SIZE
+        });
+        let [rx, ry, rw, rh] = rect;
+        let inside = (rx..=rx + rw).contains(&x) && (ry..=ry + rh).contains(&y);
+        on_handle
+            .or(inside.then_some(RectHandle::Body))
+            .map(|handle| (i, handle))
+    })
+}
+
 fn timeline_rect_timepos(timeline_rect: Rect<i16>, x: i16, src_info: &source::Info) -> f64 {
     let x_offset = x - timeline_rect.left;
     let ratio: f64 = x_offset as f64 / timeline_rect.width as f64;
@@ -64,28 +263,45 @@ pub(crate) fn draw_overlay(
         .as_ref()
         .map_or(VideoVector::new(0, 0), |present| present.dim);
     // Rect markers
-    for marker in &app_state.source_markers.rects {
-        let dim = marker
-            .rect
-            .dim
-            .to_present(app_state.src.dim, video_present_dim);
-        rs.set_size((dim.x.into(), dim.y.into()));
-        let pos = marker
-            .rect
-            .pos
-            .to_present(app_state.src.dim, video_present_dim);
-        rs.set_position((pos.x.into(), pos.y.into()));
+    let mut label = Text::new(String::new(), font, 12);
+    label.set_outline_color(Color::BLACK);
+    label.set_outline_thickness(1.0);
+    for (i, marker) in app_state.source_markers.rects.iter().enumerate() {
+        let rect @ [x, y, w, h] = rect_marker_present(marker, app_state);
+        rs.set_size((w, h));
+        rs.set_position((x, y));
         let mut fill_c = marker.color.to_sfml();
         fill_c.a = 180;
         rs.set_fill_color(fill_c);
         rw.draw_rectangle_shape(&rs, &RenderStates::DEFAULT);
+        label.set_string(marker.name.clone());
+        label.tf.position = [x + 2.0, y + 2.0];
+        label.draw(rw, &RenderStates::DEFAULT);
+        if app_state.interact.active_rect == Some(i) {
+            rs.set_fill_color(Color::WHITE);
+            rs.set_outline_color(Color::BLACK);
+            rs.set_outline_thickness(1.0);
+            rs.set_size((RECT_HANDLE_SIZE, RECT_HANDLE_SIZE));
+            for handle in RectHandle::EDGES {
+                let (hx, hy) = rect_handle_pos(handle, rect);
+                rs.set_position((hx - RECT_HANDLE_SIZE / 2.0, hy - RECT_HANDLE_SIZE / 2.0));
+                rw.draw_rectangle_shape(&rs, &RenderStates::DEFAULT);
+            }
+            rs.set_outline_thickness(0.0);
+        }
+    }
+    draw_point_markers(rw, app_state, &mut rs, &mut label, video_present_dim);
+    let timeline_rect = timeline_rect(app_state.video_area_max_dim, &app_state.src);
+    let timeline_rect_sf: Rect<f32> = timeline_rect.into_other();
+    // Top of the timeline related overlay elements, so text can be placed above them
+    let mut overlay_top = timeline_rect_sf.top;
+    if draw_filmstrip(rw, &app_state.src, timeline_rect_sf) {
+        overlay_top -= FILMSTRIP_H;
     }
     // Timeline
     rs.set_outline_color(Color::WHITE);
     rs.set_outline_thickness(2.0);
     rs.set_fill_color(Color::TRANSPARENT);
-    let timeline_rect = timeline_rect(app_state.video_area_max_dim);
-    let timeline_rect_sf: Rect<f32> = timeline_rect.into_other();
     rs.set_position(timeline_rect_sf.position());
     rs.set_size(timeline_rect_sf.size());
     rw.draw_rectangle_shape(&rs, &RenderStates::DEFAULT);
@@ -96,36 +312,79 @@ pub(crate) fn draw_overlay(
         TIMELINE_H.into(),
     ));
     rw.draw_rectangle_shape(&rs, &RenderStates::DEFAULT);
+    rs.set_outline_thickness(0.0);
+    // Keyframe ticks
+    rs.set_fill_color(Color::rgba(255, 200, 0, 200));
+    rs.set_size((1.0, 4.0));
+    let mut last_x = f32::NEG_INFINITY;
+    for &kf in &app_state.src.keyframes.times {
+        let x =
+            timeline_rect_sf.left + timeline_rect_sf.width * (kf / app_state.src.duration) as f32;
+        // Don't bother drawing ticks that would overlap
+        if x - last_x < 2.0 {
+            continue;
+        }
+        last_x = x;
+        rs.set_position((x, timeline_rect_sf.top + timeline_rect_sf.height + 2.0));
+        rw.draw_rectangle_shape(&rs, &RenderStates::DEFAULT);
+    }
     // Timespan markers
     for marker in &app_state.source_markers.timespans {
         draw_timespan_marker(
-            timeline_rect_sf.width,
-            marker.timespan.begin / app_state.src.duration,
-            &mut rs,
-            app_state.video_area_max_dim,
-            marker,
-            rw,
-        );
-        draw_timespan_marker(
-            timeline_rect_sf.width,
-            marker.timespan.end / app_state.src.duration,
+            timeline_rect_sf,
+            app_state.src.duration,
             &mut rs,
-            app_state.video_area_max_dim,
             marker,
             rw,
         );
     }
+    if let Some(lanes_rect) = gap_lanes_rect(app_state.video_area_max_dim, &app_state.src) {
+        draw_gap_lanes(rw, &mut rs, &app_state.src, lanes_rect.into_other());
+    }
+    if let Some(waveform_rect) = waveform_rect(app_state.video_area_max_dim, &app_state.src) {
+        draw_waveform(rw, &mut rs, app_state, waveform_rect.into_other());
+    }
     // Text overlay
     let mut text = Text::new(pos_string.to_owned(), font, 14);
     text.tf.position = [
         app_state.video_area_max_dim.x as f32 - 240.0,
-        timeline_rect_sf.top - 20.0,
+        overlay_top - 20.0,
     ];
     text.draw(rw, &RenderStates::DEFAULT);
+    if let Some(rgb) = app_state.hover_rgb {
+        draw_pixel_inspector(
+            rw,
+            rgb,
+            font,
+            &mut rs,
+            [
+                app_state.video_area_max_dim.x as f32 - 360.0,
+                overlay_top - 40.0,
+            ],
+        );
+    }
     if timeline_rect.contains(mouse_pos.as_other()) {
         let timepos = timeline_rect_timepos(timeline_rect, mouse_pos.x as i16, &app_state.src);
-        text.tf.position = [timeline_rect_sf.left, timeline_rect_sf.top - 20.0];
-        text.set_string(format!("Mouse time pos: {}", FfmpegTimeFmt(timepos)));
+        draw_hover_preview(
+            rw,
+            &app_state.src,
+            timepos,
+            mouse_pos.x as f32,
+            overlay_top - 24.0,
+        );
+        text.tf.position = [timeline_rect_sf.left, overlay_top - 20.0];
+        let mut hover_string = format!("Mouse time pos: {}", FfmpegTimeFmt(timepos));
+        let hovered_names: Vec<&str> = app_state
+            .source_markers
+            .timespans
+            .iter()
+            .filter(|marker| (marker.timespan.begin..=marker.timespan.end).contains(&timepos))
+            .map(|marker| marker.name.as_str())
+            .collect();
+        if !hovered_names.is_empty() {
+            hover_string.push_str(&format!(" [{}]", hovered_names.join(", ")));
+        }
+        text.set_string(hover_string);
         text.draw(rw, &RenderStates::DEFAULT);
     }
     // Texts
@@ -195,26 +454,326 @@ pub(crate) fn draw_overlay(
     }
 }
 
-fn timeline_rect(video_area_max_dim: VideoVector<Dim, Present>) -> VideoRect {
+/// Draw a swatch and the values of the colour under the mouse
+fn draw_pixel_inspector(
+    rw: &mut RenderWindow,
+    rgb: color::Rgb,
+    font: &Font,
+    rs: &mut RectangleShape,
+    [x, y]: [f32; 2],
+) {
+    let [r, g, b] = rgb;
+    rs.set_fill_color(Color::rgb(r, g, b));
+    rs.set_outline_color(Color::WHITE);
+    rs.set_outline_thickness(1.0);
+    rs.set_size((14.0, 14.0));
+    rs.set_position((x, y + 2.0));
+    rw.draw_rectangle_shape(rs, &RenderStates::DEFAULT);
+    rs.set_outline_thickness(0.0);
+    let [cy, cu, cv] = color::yuv(rgb);
+    let mut text = Text::new(
+        format!(
+            "{} rgb({r}, {g}, {b}) yuv({cy}, {cu}, {cv})",
+            color::hex(rgb)
+        ),
+        font,
+        14,
+    );
+    text.tf.position = [x + 20.0, y];
+    text.draw(rw, &RenderStates::DEFAULT);
+}
+
+/// Point markers as crosshairs, and polygon markers as outlines with their vertices
+fn draw_point_markers(
+    rw: &mut RenderWindow,
+    app_state: &AppState,
+    rs: &mut RectangleShape,
+    label: &mut Text,
+    present_dim: VideoDim<Present>,
+) {
+    let to_present = |pos: VideoPos<Src>| -> (f32, f32) {
+        let pos = pos.to_present(app_state.src.dim, present_dim);
+        (pos.x.into(), pos.y.into())
+    };
+    for marker in &app_state.source_markers.points {
+        let (x, y) = to_present(marker.pos);
+        rs.set_fill_color(marker.color.to_sfml());
+        rs.set_outline_color(Color::BLACK);
+        rs.set_outline_thickness(1.0);
+        rs.set_size((POINT_CROSS_SIZE * 2.0 + 1.0, 1.0));
+        rs.set_position((x - POINT_CROSS_SIZE, y));
+        rw.draw_rectangle_shape(rs, &RenderStates::DEFAULT);
+        rs.set_size((1.0, POINT_CROSS_SIZE * 2.0 + 1.0));
+        rs.set_position((x, y - POINT_CROSS_SIZE));
+        rw.draw_rectangle_shape(rs, &RenderStates::DEFAULT);
+        label.set_string(marker.name.clone());
+        label.tf.position = [x + 4.0, y + 2.0];
+        label.draw(rw, &RenderStates::DEFAULT);
+    }
+    rs.set_outline_thickness(0.0);
+    for marker in &app_state.source_markers.polygons {
+        let color = marker.color.to_sfml();
+        rs.set_fill_color(color);
+        let n = marker.points.len();
+        // A single vertex has no edges yet
+        let n_edges = if n > 2 { n } else { n.saturating_sub(1) };
+        for i in 0..n_edges {
+            let (ax, ay) = to_present(marker.points[i]);
+            let (bx, by) = to_present(marker.points[(i + 1) % n]);
+            let (dx, dy) = (bx - ax, by - ay);
+            rs.set_size(((dx * dx + dy * dy).sqrt(), 2.0));
+            rs.set_position((ax, ay));
+            rs.set_rotation(dy.atan2(dx).to_degrees());
+            rw.draw_rectangle_shape(rs, &RenderStates::DEFAULT);
+        }
+        rs.set_rotation(0.0);
+        rs.set_size((POLYGON_VERTEX_SIZE, POLYGON_VERTEX_SIZE));
+        for &pos in &marker.points {
+            let (x, y) = to_present(pos);
+            rs.set_position((x - POLYGON_VERTEX_SIZE / 2.0, y - POLYGON_VERTEX_SIZE / 2.0));
+            rw.draw_rectangle_shape(rs, &RenderStates::DEFAULT);
+        }
+        if let Some(&first) = marker.points.first() {
+            let (x, y) = to_present(first);
+            label.set_string(marker.name.clone());
+            label.tf.position = [x + 4.0, y + 2.0];
+            label.draw(rw, &RenderStates::DEFAULT);
+        }
+    }
+}
+
+/// Draw a strip of thumbnails right above the timeline
+///
+/// Returns whether there were thumbnails to draw
+fn draw_filmstrip(
+    rw: &mut RenderWindow,
+    src_info: &source::Info,
+    timeline_rect: Rect<f32>,
+) -> bool {
+    let scale = FILMSTRIP_H / THUMB_H as f32;
+    let tile_w = src_info.thumbnails.thumb_w() as f32 * scale;
+    if tile_w <= 0.0 || src_info.duration <= 0.0 {
+        return false;
+    }
+    let right = timeline_rect.left + timeline_rect.width;
+    let mut x = timeline_rect.left;
+    while x < right {
+        let center_ratio = (x + tile_w / 2.0 - timeline_rect.left) / timeline_rect.width;
+        let Some((texture, mut tex_rect)) = src_info
+            .thumbnails
+            .at(center_ratio as f64 * src_info.duration, src_info.duration)
+        else {
+            return false;
+        };
+        // Cut off the last tile at the end of the timeline
+        let visible_w = (right - x).min(tile_w);
+        tex_rect.width = (visible_w / scale) as i32;
+        let mut sprite = Sprite::with_texture(texture);
+        sprite.set_texture_rect(tex_rect);
+        sprite.set_position((x, timeline_rect.top - FILMSTRIP_H));
+        sprite.set_scale((scale, scale));
+        rw.draw_sprite(&sprite, &RenderStates::DEFAULT);
+        x += tile_w;
+    }
+    true
+}
+
+/// Draw the thumbnail for `time` centered above the mouse, with its bottom at `bottom`
+fn draw_hover_preview(
+    rw: &mut RenderWindow,
+    src_info: &source::Info,
+    time: f64,
+    mouse_x: f32,
+    bottom: f32,
+) {
+    let Some((texture, tex_rect)) = src_info.thumbnails.at(time, src_info.duration) else {
+        return;
+    };
+    let w = tex_rect.width as f32;
+    let mut sprite = Sprite::with_texture(texture);
+    sprite.set_texture_rect(tex_rect);
+    sprite.set_position(((mouse_x - w / 2.0).max(0.0), bottom - THUMB_H as f32));
+    rw.draw_sprite(&sprite, &RenderStates::DEFAULT);
+}
+
+fn timeline_rect(
+    video_area_max_dim: VideoVector<Dim, Present>,
+    src_info: &source::Info,
+) -> VideoRect {
     let left = TIMELINE_MARGIN;
-    let top = video_area_max_dim.y - TIMELINE_MARGIN;
+    let mut top = video_area_max_dim.y - TIMELINE_MARGIN;
+    // Make room for the lanes below
+    let mut lanes_h = gap_lanes_h(src_info);
+    if !src_info.waveform.is_empty() {
+        lanes_h += WAVEFORM_H;
+    }
+    if lanes_h > 0 {
+        top -= LANES_GAP + lanes_h;
+    }
     let width = video_area_max_dim.x - TIMELINE_MARGIN * 2;
     let height = TIMELINE_H;
     Rect::new(left, top, width, height)
 }
 
+fn waveform_rect(
+    video_area_max_dim: VideoVector<Dim, Present>,
+    src_info: &source::Info,
+) -> Option<VideoRect> {
+    if src_info.waveform.is_empty() {
+        return None;
+    }
+    let timeline_rect = timeline_rect(video_area_max_dim, src_info);
+    Some(Rect::new(
+        timeline_rect.left,
+        timeline_rect.top + TIMELINE_H + LANES_GAP + gap_lanes_h(src_info),
+        timeline_rect.width,
+        WAVEFORM_H,
+    ))
+}
+
+/// Silence and black frame intervals that have been detected, with the color of their lane
+fn gap_lanes(src_info: &source::Info) -> impl Iterator<Item = (&[TimeSpan], Color)> {
+    [
+        (&src_info.silence, Color::rgb(120, 220, 120)),
+        (&src_info.black, Color::rgb(170, 170, 170)),
+    ]
+    .into_iter()
+    .filter(|(gaps, _)| !gaps.spans.is_empty())
+    .map(|(gaps, color)| (&gaps.spans[..], color))
+}
+
+fn gap_lanes_h(src_info: &source::Info) -> VideoMag {
+    gap_lanes(src_info).count() as VideoMag * (GAP_LANE_H + GAP_LANE_SPACING)
+}
+
+/// The area of all detection lanes, between the timeline and the waveform
+fn gap_lanes_rect(
+    video_area_max_dim: VideoVector<Dim, Present>,
+    src_info: &source::Info,
+) -> Option<VideoRect> {
+    let h = gap_lanes_h(src_info);
+    if h == 0 {
+        return None;
+    }
+    let timeline_rect = timeline_rect(video_area_max_dim, src_info);
+    Some(Rect::new(
+        timeline_rect.left,
+        timeline_rect.top + TIMELINE_H + LANES_GAP,
+        timeline_rect.width,
+        h,
+    ))
+}
+
+fn draw_gap_lanes(
+    rw: &mut RenderWindow,
+    rs: &mut RectangleShape,
+    src_info: &source::Info,
+    rect: Rect<f32>,
+) {
+    let time_to_x = |time: f64| rect.left + (time / src_info.duration) as f32 * rect.width;
+    let lane_h = f32::from(GAP_LANE_H);
+    rs.set_outline_thickness(0.0);
+    for (i, (spans, color)) in gap_lanes(src_info).enumerate() {
+        let top = rect.top + i as f32 * f32::from(GAP_LANE_H + GAP_LANE_SPACING);
+        rs.set_fill_color(Color::rgba(0, 0, 0, 120));
+        rs.set_position((rect.left, top));
+        rs.set_size((rect.width, lane_h));
+        rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+        rs.set_fill_color(color);
+        for span in spans {
+            let (begin_x, end_x) = (time_to_x(span.begin), time_to_x(span.end));
+            rs.set_position((begin_x, top));
+            rs.set_size(((end_x - begin_x).max(1.0), lane_h));
+            rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+        }
+    }
+}
+
+/// The time range shown in the waveform lane, centered on the playhead when zoomed in
+fn waveform_window(src_info: &source::Info, zoom: f64) -> (f64, f64) {
+    let len = src_info.duration / zoom;
+    let begin = (src_info.time_pos - len / 2.0).clamp(0.0, (src_info.duration - len).max(0.0));
+    (begin, begin + len)
+}
+
+fn draw_waveform(
+    rw: &mut RenderWindow,
+    rs: &mut RectangleShape,
+    app_state: &AppState,
+    rect: Rect<f32>,
+) {
+    let (begin, end) = waveform_window(&app_state.src, app_state.interact.waveform_zoom);
+    let time_to_x = |time: f64| rect.left + ((time - begin) / (end - begin)) as f32 * rect.width;
+    let in_window = |time: f64| (begin..=end).contains(&time);
+    rs.set_outline_thickness(0.0);
+    rs.set_fill_color(Color::rgba(0, 0, 0, 120));
+    rs.set_position(rect.position());
+    rs.set_size(rect.size());
+    rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+    // Peaks, one column per pixel
+    let mid_y = rect.top + rect.height / 2.0;
+    let half_h = rect.height / 2.0;
+    let col_dur = (end - begin) / rect.width as f64;
+    rs.set_fill_color(Color::rgb(90, 200, 255));
+    for col in 0..rect.width as u32 {
+        let t = begin + col as f64 * col_dur;
+        let Some(peak) = app_state.src.waveform.peak(t, t + col_dur) else {
+            continue;
+        };
+        let top = mid_y - f32::from(peak.max) / f32::from(i16::MAX) * half_h;
+        let bottom = mid_y - f32::from(peak.min) / f32::from(i16::MAX) * half_h;
+        rs.set_position((rect.left + col as f32, top));
+        rs.set_size((1.0, (bottom - top).max(1.0)));
+        rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+    }
+    // Sub timestamps
+    rs.set_size((1.0, rect.height));
+    if let Some(subs) = &app_state.subs {
+        rs.set_fill_color(Color::rgba(255, 255, 255, 160));
+        for &stamp in subs.time_stamps.iter().filter(|&&t| in_window(t)) {
+            rs.set_position((time_to_x(stamp), rect.top));
+            rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+        }
+    }
+    // Timespan markers
+    for marker in &app_state.source_markers.timespans {
+        rs.set_fill_color(marker.color.to_sfml());
+        for time in [marker.timespan.begin, marker.timespan.end] {
+            if in_window(time) {
+                rs.set_position((time_to_x(time), rect.top));
+                rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+            }
+        }
+    }
+    // Playhead
+    rs.set_fill_color(Color::RED);
+    rs.set_position((time_to_x(app_state.src.time_pos), rect.top));
+    rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+}
+
+/// Draw a timespan as a filled range on the timeline, with handles at its edges
 fn draw_timespan_marker(
-    full_w: f32,
-    pos_ratio: f64,
+    timeline_rect: Rect<f32>,
+    duration: f64,
     rs: &mut RectangleShape,
-    video_area_max_dim: VideoVector<Dim, Present>,
-    marker: &crate::TimespanMarker,
+    marker: &TimespanMarker,
     rw: &mut RenderWindow,
 ) {
-    let x = (full_w * pos_ratio as f32) + 20.0;
-    rs.set_position((x, (video_area_max_dim.y - 60) as f32));
-    rs.set_fill_color(marker.color.to_sfml());
-    rs.set_size((3.0, 14.0));
+    let begin_x =
+        timeline_rect.left + timeline_rect.width * (marker.timespan.begin / duration) as f32;
+    let end_x = timeline_rect.left + timeline_rect.width * (marker.timespan.end / duration) as f32;
+    let mut color = marker.color.to_sfml();
     rs.set_outline_thickness(0.0);
+    color.a = 110;
+    rs.set_fill_color(color);
+    rs.set_position((begin_x, timeline_rect.top));
+    rs.set_size((end_x - begin_x, timeline_rect.height));
     rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+    color.a = 255;
+    rs.set_fill_color(color);
+    rs.set_size((3.0, timeline_rect.height + 6.0));
+    for x in [begin_x - 1.0, end_x - 2.0] {
+        rs.set_position((x, timeline_rect.top - 3.0));
+        rw.draw_rectangle_shape(&*rs, &RenderStates::DEFAULT);
+    }
 }
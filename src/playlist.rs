@@ -0,0 +1,110 @@
+//! A list of files to work on one after another, each with its own markers
+
+use {
+    crate::SourceMarkers,
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Extensions of files picked up when opening a folder
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp4", "mkv", "webm", "mov", "avi", "m4v", "flv", "wmv", "ts", "m2ts", "mpg", "mpeg", "mp3",
+    "flac", "wav", "ogg", "opus", "m4a",
+];
+
+#[derive(Default)]
+pub struct Playlist {
+    pub files: Vec<String>,
+    /// Markers of the files that are not currently loaded
+    markers: HashMap<String, SourceMarkers>,
+}
+
+impl Playlist {
+    /// All media files directly in `dir`, sorted by name
+    pub fn from_dir(dir: &Path) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_media = path.extension().is_some_and(|ext| {
+                MEDIA_EXTENSIONS
+                    .iter()
+                    .any(|media_ext| ext.eq_ignore_ascii_case(media_ext))
+            });
+            if is_media && path.is_file() {
+                files.push(path.display().to_string());
+            }
+        }
+        files.sort();
+        if files.is_empty() {
+            anyhow::bail!("No media files in {}", dir.display());
+        }
+        Ok(Self {
+            files,
+            ..Default::default()
+        })
+    }
+    /// Read an m3u (or plain list of paths) playlist
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let files = parse_m3u(&text, path.parent().unwrap_or(Path::new("")));
+        if files.is_empty() {
+            anyhow::bail!("No files in playlist {}", path.display());
+        }
+        Ok(Self {
+            files,
+            ..Default::default()
+        })
+    }
+    pub fn position(&self, path: &str) -> Option<usize> {
+        self.files.iter().position(|file| file == path)
+    }
+    /// Keep the markers of the file being left, and bring back the ones of the file being loaded
+    ///
+    /// Files outside the playlist don't swap markers, so they can still be carried over.
+    pub fn switch_markers(&mut self, old_path: &str, new_path: &str, markers: &mut SourceMarkers) {
+        if old_path == new_path
+            || self.position(old_path).is_none()
+            || self.position(new_path).is_none()
+        {
+            return;
+        }
+        let new_markers = self.markers.remove(new_path).unwrap_or_default();
+        self.markers
+            .insert(old_path.to_owned(), std::mem::replace(markers, new_markers));
+    }
+    /// Markers of a file that is not currently loaded
+    pub fn markers(&self, path: &str) -> Option<&SourceMarkers> {
+        self.markers.get(path)
+    }
+}
+
+/// Paths in an m3u playlist, relative ones resolved against `base_dir`
+fn parse_m3u(text: &str, base_dir: &Path) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            // URLs are passed to mpv as they are
+            if line.contains("://") {
+                return line.to_owned();
+            }
+            let path = PathBuf::from(line);
+            if path.is_absolute() {
+                line.to_owned()
+            } else {
+                base_dir.join(path).display().to_string()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_m3u() {
+    let m3u = "#EXTM3U\n#EXTINF:123,Title\nclips/a.mp4\n\n/abs/b.mkv\r\nhttps://x.org/c.webm\n";
+    assert_eq!(
+        parse_m3u(m3u, Path::new("/lists")),
+        ["/lists/clips/a.mp4", "/abs/b.mkv", "https://x.org/c.webm"]
+    );
+}
@@ -0,0 +1,128 @@
+use crate::{
+    RectHandle,
+    coords::{Src, VideoDim, VideoMag, VideoRect},
+};
+
+/// Constraints a rect marker is kept within while editing it
+pub struct RectConstraints {
+    /// Width:height ratio to keep
+    pub aspect: Option<(u16, u16)>,
+    /// Position and size are rounded down to a multiple of this
+    pub align: VideoMag,
+    pub min_w: VideoMag,
+    pub min_h: VideoMag,
+    /// Maximum width. Zero means unlimited.
+    pub max_w: VideoMag,
+    /// Maximum height. Zero means unlimited.
+    pub max_h: VideoMag,
+    pub clamp_to_frame: bool,
+}
+
+impl Default for RectConstraints {
+    fn default() -> Self {
+        Self {
+            aspect: None,
+            align: 1,
+            min_w: 0,
+            min_h: 0,
+            max_w: 0,
+            max_h: 0,
+            clamp_to_frame: true,
+        }
+    }
+}
+
+pub const ALIGN_CHOICES: [VideoMag; 4] = [1, 2, 8, 16];
+
+impl RectConstraints {
+    /// Return `rect` adjusted to satisfy the constraints
+    ///
+    /// `handle` is the handle being dragged, if any. Dragging the top or bottom edge
+    /// makes the height the leading dimension for the aspect ratio.
+    pub fn apply(
+        &self,
+        rect: VideoRect<Src>,
+        frame: VideoDim<Src>,
+        handle: Option<RectHandle>,
+    ) -> VideoRect<Src> {
+        let mut rect = rect;
+        let align = self.align.max(1);
+        let (mut w, mut h) = (i32::from(rect.dim.x), i32::from(rect.dim.y));
+        if self.max_w > 0 {
+            w = w.min(self.max_w.into());
+        }
+        if self.max_h > 0 {
+            h = h.min(self.max_h.into());
+        }
+        w = w.max(self.min_w.into());
+        h = h.max(self.min_h.into());
+        if self.clamp_to_frame && frame.x > 0 && frame.y > 0 {
+            w = w.min(frame.x.into());
+            h = h.min(frame.y.into());
+        }
+        if let Some((aw, ah)) = self.aspect
+            && aw > 0
+            && ah > 0
+        {
+            let (aw, ah) = (i32::from(aw), i32::from(ah));
+            let height_leads = matches!(handle, Some(RectHandle::Top | RectHandle::Bottom));
+            if height_leads {
+                w = h * aw / ah;
+            } else {
+                h = w * ah / aw;
+            }
+            // Keep the ratio when the leading side made the other one too large
+            if self.clamp_to_frame && frame.x > 0 && frame.y > 0 {
+                if w > frame.x.into() {
+                    w = frame.x.into();
+                    h = w * ah / aw;
+                }
+                if h > frame.y.into() {
+                    h = frame.y.into();
+                    w = h * aw / ah;
+                }
+            }
+        }
+        let align = i32::from(align);
+        w -= w % align;
+        h -= h % align;
+        rect.dim = VideoDim::new(w as VideoMag, h as VideoMag);
+        rect.pos.x -= rect.pos.x % align as VideoMag;
+        rect.pos.y -= rect.pos.y % align as VideoMag;
+        if self.clamp_to_frame && frame.x > 0 && frame.y > 0 {
+            rect.pos.x = rect.pos.x.clamp(0, (frame.x - rect.dim.x).max(0));
+            rect.pos.y = rect.pos.y.clamp(0, (frame.y - rect.dim.y).max(0));
+        }
+        rect
+    }
+}
+
+#[test]
+fn test_rect_constraints() {
+    let frame = VideoDim::new(1920, 1080);
+    let vertical = RectConstraints {
+        aspect: Some((9, 16)),
+        align: 2,
+        ..Default::default()
+    };
+    let rect = vertical.apply(VideoRect::new(1801, 3, 301, 200), frame, None);
+    assert_eq!((rect.dim.x, rect.dim.y), (300, 534));
+    assert_eq!((rect.pos.x, rect.pos.y), (1620, 2));
+    // Way too tall for the frame, the ratio should still hold
+    let rect = vertical.apply(VideoRect::new(0, 0, 1000, 100), frame, None);
+    assert_eq!((rect.dim.x, rect.dim.y), (606, 1080));
+    let rect = vertical.apply(
+        VideoRect::new(0, 0, 1000, 160),
+        frame,
+        Some(RectHandle::Bottom),
+    );
+    assert_eq!((rect.dim.x, rect.dim.y), (90, 160));
+    let sized = RectConstraints {
+        min_w: 64,
+        max_h: 100,
+        clamp_to_frame: false,
+        ..Default::default()
+    };
+    let rect = sized.apply(VideoRect::new(-5, 0, 10, 500), frame, None);
+    assert_eq!((rect.pos.x, rect.dim.x, rect.dim.y), (-5, 64, 100));
+}
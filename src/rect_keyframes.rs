@@ -0,0 +1,133 @@
+use crate::coords::{Src, VideoMag, VideoRect};
+
+/// The rect a marker has at a point in time
+#[derive(Clone, Copy)]
+pub struct RectKeyframe {
+    pub time: f64,
+    pub rect: VideoRect<Src>,
+    /// How to interpolate towards the next keyframe
+    pub easing: Easing,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Smoothstep, slow at both ends
+    Ease,
+}
+
+impl Easing {
+    pub const ALL: [Self; 2] = [Self::Linear, Self::Ease];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Ease => "Ease",
+        }
+    }
+    fn apply(self, p: f64) -> f64 {
+        match self {
+            Self::Linear => p,
+            Self::Ease => p * p * (3.0 - 2.0 * p),
+        }
+    }
+    /// The same as [`Self::apply`], as an ffmpeg expression
+    fn expr(self, p: &str) -> String {
+        match self {
+            Self::Linear => p.to_owned(),
+            Self::Ease => format!("{p}*{p}*(3-2*{p})"),
+        }
+    }
+}
+
+/// Insert a keyframe, replacing an existing one at (almost) the same time
+pub fn set(keyframes: &mut Vec<RectKeyframe>, kf: RectKeyframe, tolerance: f64) {
+    match keyframes
+        .iter_mut()
+        .find(|old| (old.time - kf.time).abs() <= tolerance)
+    {
+        Some(old) => {
+            old.time = kf.time;
+            old.rect = kf.rect;
+        }
+        None => {
+            let idx = keyframes.partition_point(|old| old.time < kf.time);
+            keyframes.insert(idx, kf);
+        }
+    }
+}
+
+/// The interpolated rect at `time`. `keyframes` must be sorted by time.
+pub fn rect_at(keyframes: &[RectKeyframe], time: f64) -> Option<VideoRect<Src>> {
+    let first = keyframes.first()?;
+    let last = keyframes.last()?;
+    if time <= first.time {
+        return Some(first.rect);
+    }
+    if time >= last.time {
+        return Some(last.rect);
+    }
+    let idx = keyframes.partition_point(|kf| kf.time <= time);
+    let (a, b) = (&keyframes[idx - 1], &keyframes[idx]);
+    let p = a.easing.apply((time - a.time) / (b.time - a.time));
+    let lerp =
+        |va: VideoMag, vb: VideoMag| (f64::from(va) + f64::from(vb - va) * p).round() as VideoMag;
+    Some(VideoRect::new(
+        lerp(a.rect.pos.x, b.rect.pos.x),
+        lerp(a.rect.pos.y, b.rect.pos.y),
+        lerp(a.rect.dim.x, b.rect.dim.x),
+        lerp(a.rect.dim.y, b.rect.dim.y),
+    ))
+}
+
+/// An ffmpeg expression (in terms of `t`) for one component of the animated rect
+///
+/// This is a chain of nested `if`s, one per keyframe segment.
+/// `keyframes` must be sorted by time, and not be empty.
+pub fn ffmpeg_expr(
+    keyframes: &[RectKeyframe],
+    get: impl Fn(&VideoRect<Src>) -> VideoMag,
+) -> String {
+    let (Some(first), Some(last)) = (keyframes.first(), keyframes.last()) else {
+        return String::new();
+    };
+    if keyframes.iter().all(|kf| get(&kf.rect) == get(&first.rect)) {
+        return get(&first.rect).to_string();
+    }
+    let mut expr = get(&last.rect).to_string();
+    for [a, b] in keyframes.array_windows().rev() {
+        let (va, vb) = (get(&a.rect), get(&b.rect));
+        let segment = if va == vb {
+            va.to_string()
+        } else {
+            let p = format!("((t-{})/{})", a.time, b.time - a.time);
+            format!("{va}+({})*{}", vb - va, a.easing.expr(&p))
+        };
+        expr = format!("if(lt(t,{}),{segment},{expr})", b.time);
+    }
+    format!("if(lt(t,{}),{},{expr})", first.time, get(&first.rect))
+}
+
+#[test]
+fn test_rect_keyframes() {
+    let mut kfs = Vec::new();
+    let kf = |time, x| RectKeyframe {
+        time,
+        rect: VideoRect::new(x, 0, 10, 10),
+        easing: Easing::Linear,
+    };
+    set(&mut kfs, kf(2.0, 100), 0.01);
+    set(&mut kfs, kf(0.0, 0), 0.01);
+    set(&mut kfs, kf(2.001, 50), 0.01);
+    assert_eq!(kfs.len(), 2);
+    assert_eq!(rect_at(&kfs, -1.0).unwrap().pos.x, 0);
+    assert_eq!(rect_at(&kfs, 1.0).unwrap().pos.x, 25);
+    assert_eq!(rect_at(&kfs, 5.0).unwrap().pos.x, 50);
+    assert_eq!(
+        ffmpeg_expr(&kfs, |r| r.pos.x),
+        "if(lt(t,0),0,if(lt(t,2.001),0+(50)*((t-0)/2.001),50))"
+    );
+    assert_eq!(ffmpeg_expr(&kfs, |r| r.pos.y), "0");
+    kfs[0].easing = Easing::Ease;
+    assert_eq!(rect_at(&kfs, 2.001 / 4.0).unwrap().pos.x, 8);
+}
@@ -1,9 +1,80 @@
 use crate::coords::{Src, VideoDim};
 
+pub mod gaps;
+pub mod keyframes;
+pub mod scenes;
+pub mod thumbnails;
+pub mod waveform;
+
 pub struct Info {
     pub dim: VideoDim<Src>,
     pub w_h_ratio: f64,
     pub duration: f64,
     pub time_pos: f64,
+    /// File the source markers belong to
+    ///
+    /// Only updated once mpv has loaded a file. mpv's `path` already changes when loading begins,
+    /// and the playlist relies on this still being the previous file to save its markers.
     pub path: String,
+    /// Frames per second of the source. Zero if unknown.
+    pub fps: f64,
+    pub keyframes: keyframes::Keyframes,
+    pub thumbnails: thumbnails::Thumbnails,
+    pub waveform: waveform::Waveform,
+    pub scenes: scenes::Scenes,
+    pub silence: gaps::Gaps,
+    pub black: gaps::Gaps,
+}
+
+impl Info {
+    /// Info of a file that hasn't been loaded, only knowing its path
+    pub fn new(path: String) -> Self {
+        Self {
+            dim: VideoDim::new(0, 0),
+            w_h_ratio: 1.0,
+            duration: 0.0,
+            time_pos: 0.0,
+            path,
+            fps: 0.0,
+            keyframes: Default::default(),
+            thumbnails: Default::default(),
+            waveform: Default::default(),
+            scenes: Default::default(),
+            silence: Default::default(),
+            black: Default::default(),
+        }
+    }
+    /// Snap a timestamp to the beginning of the frame it falls into
+    ///
+    /// This assumes a constant frame rate with frames starting at 0,
+    /// so it's off for variable frame rate sources.
+    /// Returns `time` unchanged if the fps is unknown.
+    pub fn snap_to_frame(&self, time: f64) -> f64 {
+        snap_to_frame(time, self.fps)
+    }
+    /// How far a timestamp can be from a keyframe to still count as aligned
+    pub fn keyframe_tolerance(&self) -> f64 {
+        if self.fps > 0.0 {
+            0.5 / self.fps
+        } else {
+            0.001
+        }
+    }
+}
+
+fn snap_to_frame(time: f64, fps: f64) -> f64 {
+    if fps <= 0.0 {
+        return time;
+    }
+    // Add a tiny bias so timestamps that are already on a frame boundary
+    // (but slightly below due to float error) don't snap to the previous frame
+    (time * fps + 1e-6).floor() / fps
+}
+
+#[test]
+fn test_snap_to_frame() {
+    assert_eq!(snap_to_frame(1.234, 0.0), 1.234);
+    assert_eq!(snap_to_frame(1.0, 25.0), 1.0);
+    assert_eq!(snap_to_frame(1.05, 25.0), 1.04);
+    assert_eq!(snap_to_frame(0.1 + 0.2, 10.0), 0.3);
 }
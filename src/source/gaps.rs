@@ -0,0 +1,195 @@
+//! Silence and black frame detection, for finding dead air to cut out
+
+use {
+    crate::{TimeSpan, bg_job::BgJob, ffmpeg::tools::Tools},
+    std::process::Stdio,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Silence,
+    Black,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Silence => "silence",
+            Self::Black => "black",
+        }
+    }
+    fn filter(self, params: Params) -> String {
+        match self {
+            Self::Silence => format!(
+                "silencedetect=noise={}dB:d={}",
+                params.threshold, params.min_duration
+            ),
+            Self::Black => format!(
+                "scale=320:-2,blackdetect=d={}:pix_th={}",
+                params.min_duration, params.threshold
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Params {
+    /// Noise level in dB for silence, ratio (0-1) below which a pixel counts as black for black
+    pub threshold: f64,
+    /// Intervals shorter than this (in seconds) are ignored
+    pub min_duration: f64,
+}
+
+impl Params {
+    pub const SILENCE: Self = Self {
+        threshold: -30.0,
+        min_duration: 0.5,
+    };
+    pub const BLACK: Self = Self {
+        threshold: 0.1,
+        min_duration: 0.5,
+    };
+}
+
+/// Intervals of silence or black frames found in the source
+#[derive(Default)]
+pub struct Gaps {
+    pub spans: Vec<TimeSpan>,
+    job: Option<BgJob<Vec<TimeSpan>>>,
+}
+
+impl Gaps {
+    /// Start looking for gaps in `path` in the background
+    pub fn detect(&mut self, path: &str, kind: Kind, params: Params, duration: f64, tools: &Tools) {
+        let (path, tools) = (path.to_owned(), tools.clone());
+        self.spans.clear();
+        self.job = Some(BgJob::spawn(move || {
+            detect_gaps(&tools, &path, kind, params, duration)
+        }));
+    }
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = &mut self.job
+            && let Some(result) = job.poll()
+        {
+            self.job = None;
+            self.spans = result?;
+        }
+        Ok(())
+    }
+    pub fn is_detecting(&self) -> bool {
+        self.job.is_some()
+    }
+}
+
+fn detect_gaps(
+    tools: &Tools,
+    path: &str,
+    kind: Kind,
+    params: Params,
+    duration: f64,
+) -> anyhow::Result<Vec<TimeSpan>> {
+    let (map, filter_opt) = match kind {
+        Kind::Silence => ("0:a:0", "-af"),
+        Kind::Black => ("0:v:0", "-vf"),
+    };
+    let out = tools
+        .ffmpeg()
+        .args(["-hide_banner", "-nostdin", "-nostats", "-i"])
+        .arg(path)
+        .args(["-map", map, filter_opt])
+        .arg(kind.filter(params))
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if !out.status.success() {
+        anyhow::bail!(
+            "{} detection failed: {}",
+            kind.name(),
+            stderr.lines().last().unwrap_or_default().trim()
+        );
+    }
+    Ok(parse_intervals(&stderr, kind, duration))
+}
+
+/// Parse the `<kind>_start:` and `<kind>_end:` values the detect filters log
+///
+/// `silencedetect` logs them on separate lines, `blackdetect` on the same one.
+/// An interval that is still open at the end of the log lasts until `duration`.
+fn parse_intervals(log: &str, kind: Kind, duration: f64) -> Vec<TimeSpan> {
+    let start_key = format!("{}_start:", kind.name());
+    let end_key = format!("{}_end:", kind.name());
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let (_, rest) = line.split_once(key)?;
+        rest.split_whitespace().next()?.parse().ok()
+    };
+    let mut spans = Vec::new();
+    let mut begin = None;
+    for line in log.lines() {
+        if let Some(time) = value_after(line, &start_key) {
+            begin = Some(time);
+        }
+        if let Some(end) = value_after(line, &end_key) {
+            spans.push(TimeSpan {
+                begin: begin.take().unwrap_or(0.0),
+                end,
+            });
+        }
+    }
+    if let Some(begin) = begin
+        && duration > begin
+    {
+        spans.push(TimeSpan {
+            begin,
+            end: duration,
+        });
+    }
+    spans
+}
+
+/// Everything in `0..duration` that is not covered by the (sorted) `spans`
+pub fn complement(spans: &[TimeSpan], duration: f64) -> Vec<TimeSpan> {
+    let mut out = Vec::new();
+    let mut begin = 0.0;
+    for span in spans {
+        if span.begin > begin {
+            out.push(TimeSpan {
+                begin,
+                end: span.begin,
+            });
+        }
+        begin = f64::max(begin, span.end);
+    }
+    if duration > begin {
+        out.push(TimeSpan {
+            begin,
+            end: duration,
+        });
+    }
+    out
+}
+
+#[test]
+fn test_gaps() {
+    let pairs = |spans: Vec<TimeSpan>| -> Vec<(f64, f64)> {
+        spans.into_iter().map(|s| (s.begin, s.end)).collect()
+    };
+    let silence = "\
+[silencedetect @ 0x5640] silence_start: 1.5
+[silencedetect @ 0x5640] silence_end: 3.25 | silence_duration: 1.75
+size=N/A time=00:00:09.00 bitrate=N/A speed= 400x
+[silencedetect @ 0x5640] silence_start: 8
+";
+    assert_eq!(
+        pairs(parse_intervals(silence, Kind::Silence, 10.0)),
+        [(1.5, 3.25), (8.0, 10.0)]
+    );
+    let black = "\
+[blackdetect @ 0x5640] black_start:0 black_end:2.002 black_duration:2.002
+[blackdetect @ 0x5640] black_start:5.5 black_end:6 black_duration:0.5
+";
+    let spans = parse_intervals(black, Kind::Black, 10.0);
+    assert_eq!(pairs(spans.clone()), [(0.0, 2.002), (5.5, 6.0)]);
+    assert_eq!(pairs(complement(&spans, 10.0)), [(2.002, 5.5), (6.0, 10.0)]);
+    assert_eq!(pairs(complement(&[], 4.0)), [(0.0, 4.0)]);
+}
@@ -0,0 +1,121 @@
+use crate::{bg_job::BgJob, ffmpeg::tools::Tools};
+
+/// Keyframe timestamps of the first video stream of the source
+#[derive(Default)]
+pub struct Keyframes {
+    /// Sorted timestamps of keyframes
+    pub times: Vec<f64>,
+    job: Option<BgJob<Vec<f64>>>,
+}
+
+impl Keyframes {
+    /// Start indexing the keyframes of the file at `path` in the background
+    pub fn index(&mut self, path: &str, tools: &Tools) {
+        self.times.clear();
+        let (path, tools) = (path.to_owned(), tools.clone());
+        self.job = Some(BgJob::spawn(move || probe_keyframes(&tools, &path)));
+    }
+    /// Take the result of indexing, if it finished
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = &mut self.job
+            && let Some(result) = job.poll()
+        {
+            self.job = None;
+            self.times = result?;
+        }
+        Ok(())
+    }
+    #[must_use]
+    pub fn is_indexing(&self) -> bool {
+        self.job.is_some()
+    }
+    /// The last keyframe at or before `time`
+    pub fn prev(&self, time: f64) -> Option<f64> {
+        let idx = self.times.partition_point(|&t| t <= time);
+        idx.checked_sub(1).map(|idx| self.times[idx])
+    }
+    /// The first keyframe after `time`
+    pub fn next(&self, time: f64) -> Option<f64> {
+        let idx = self.times.partition_point(|&t| t <= time);
+        self.times.get(idx).copied()
+    }
+    /// Whether `time` is within `tolerance` of a keyframe
+    ///
+    /// Returns `true` if no keyframes are known, since we can't tell.
+    pub fn is_aligned(&self, time: f64, tolerance: f64) -> bool {
+        if self.times.is_empty() {
+            return true;
+        }
+        let near = |kf: Option<f64>| kf.is_some_and(|kf| (kf - time).abs() <= tolerance);
+        near(self.prev(time)) || near(self.next(time))
+    }
+}
+
+/// Use packet flags instead of decoding, so this is fast even for long files
+///
+/// The times are relative to the start time of the file, like mpv's `time-pos` and `-ss`.
+fn probe_keyframes(tools: &Tools, path: &str) -> anyhow::Result<Vec<f64>> {
+    let out = tools
+        .ffprobe()
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time,flags:format=start_time",
+            "-of",
+            "csv",
+        ])
+        .arg(path)
+        .output()?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(parse_packet_csv(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// Keyframe times from `packet,pts_time,flags` lines, rebased on the `format,start_time` line
+fn parse_packet_csv(csv: &str) -> Vec<f64> {
+    let start_time = csv
+        .lines()
+        .find_map(|line| line.strip_prefix("format,")?.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let mut times: Vec<f64> = csv
+        .lines()
+        .filter_map(|line| {
+            let (pts, flags) = line.strip_prefix("packet,")?.split_once(',')?;
+            if !flags.starts_with('K') {
+                return None;
+            }
+            pts.parse::<f64>().ok().map(|pts| pts - start_time)
+        })
+        .collect();
+    // Packets are in decode order, not presentation order
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+    times
+}
+
+#[test]
+fn test_parse_packet_csv() {
+    let csv = "packet,0.000000,K__\npacket,0.040000,___\npacket,N/A,K__\n\
+               packet,2.000000,K_\npacket,1.960000,__\nformat,N/A\n";
+    let kfs = Keyframes {
+        times: parse_packet_csv(csv),
+        job: None,
+    };
+    assert_eq!(kfs.times, [0.0, 2.0]);
+    assert_eq!(kfs.prev(1.5), Some(0.0));
+    assert_eq!(kfs.prev(2.0), Some(2.0));
+    assert_eq!(kfs.next(1.5), Some(2.0));
+    assert_eq!(kfs.next(2.0), None);
+    assert!(kfs.is_aligned(1.99, 0.02));
+    assert!(!kfs.is_aligned(1.0, 0.02));
+    // MPEG-TS files usually don't start at 0
+    let csv = "packet,1.400000,K_\npacket,3.400000,K_\nformat,1.400000\n";
+    assert_eq!(parse_packet_csv(csv), [0.0, 2.0]);
+}
@@ -0,0 +1,139 @@
+//! Scene change detection, for splitting the video into shots
+
+use {
+    crate::{TimeSpan, bg_job::BgJob, ffmpeg::tools::Tools},
+    std::process::Stdio,
+};
+
+#[derive(Default)]
+pub struct Scenes {
+    /// Scene change score (0-1) of every frame, with its time
+    pub scores: Vec<(f64, f32)>,
+    job: Option<BgJob<Vec<(f64, f32)>>>,
+}
+
+impl Scenes {
+    /// Start scoring the frames of `path` in the background
+    pub fn detect(&mut self, path: &str, tools: &Tools) {
+        let (path, tools) = (path.to_owned(), tools.clone());
+        self.scores.clear();
+        self.job = Some(BgJob::spawn(move || score_frames(&tools, &path)));
+    }
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = &mut self.job
+            && let Some(result) = job.poll()
+        {
+            self.job = None;
+            self.scores = result?;
+        }
+        Ok(())
+    }
+    pub fn is_detecting(&self) -> bool {
+        self.job.is_some()
+    }
+}
+
+/// Scores are computed on downscaled frames, which is much faster and just as good for this
+fn score_frames(tools: &Tools, path: &str) -> anyhow::Result<Vec<(f64, f32)>> {
+    let out = tools
+        .ffmpeg()
+        .args(["-hide_banner", "-nostdin", "-nostats", "-i"])
+        .arg(path)
+        .args([
+            "-map",
+            "0:v:0",
+            "-vf",
+            "scale=320:-2,select='gte(scene,0)',metadata=print:key=lavfi.scene_score",
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if !out.status.success() {
+        anyhow::bail!(
+            "Scene detection failed: {}",
+            stderr.lines().last().unwrap_or_default().trim()
+        );
+    }
+    Ok(parse_metadata_print(&stderr))
+}
+
+/// Parse the `pts_time:` and `lavfi.scene_score=` lines the `metadata` filter logs for each frame
+fn parse_metadata_print(log: &str) -> Vec<(f64, f32)> {
+    let mut scores = Vec::new();
+    let mut time = None;
+    for line in log.lines() {
+        if let Some((_, rest)) = line.split_once("pts_time:") {
+            time = rest.split_whitespace().next().and_then(|t| t.parse().ok());
+        } else if let Some((_, score)) = line.split_once("lavfi.scene_score=")
+            && let Some(time) = time.take()
+            && let Ok(score) = score.trim().parse()
+        {
+            scores.push((time, score));
+        }
+    }
+    scores
+}
+
+/// Split `0..duration` into shots at the frames scoring above `threshold`
+///
+/// Cuts that would make a shot shorter than `min_duration` are skipped, merging it with its neighbour.
+pub fn shots(
+    scores: &[(f64, f32)],
+    threshold: f32,
+    min_duration: f64,
+    duration: f64,
+) -> Vec<TimeSpan> {
+    let mut shots: Vec<TimeSpan> = Vec::new();
+    let mut begin = 0.0;
+    for &(time, score) in scores {
+        if score <= threshold || time - begin < min_duration {
+            continue;
+        }
+        shots.push(TimeSpan { begin, end: time });
+        begin = time;
+    }
+    if duration > begin {
+        match shots.last_mut() {
+            Some(last) if duration - begin < min_duration => last.end = duration,
+            _ => shots.push(TimeSpan {
+                begin,
+                end: duration,
+            }),
+        }
+    }
+    shots
+}
+
+#[test]
+fn test_scenes() {
+    let log = "\
+[Parsed_metadata_2 @ 0x55d] frame:0    pts:0       pts_time:0
+[Parsed_metadata_2 @ 0x55d] lavfi.scene_score=0.000000
+[Parsed_metadata_2 @ 0x55d] frame:1    pts:512     pts_time:1.5
+[Parsed_metadata_2 @ 0x55d] lavfi.scene_score=0.812000
+";
+    assert_eq!(parse_metadata_print(log), [(0.0, 0.0), (1.5, 0.812)]);
+    let scores = [
+        (0.0, 0.0),
+        (2.0, 0.9),
+        (2.5, 0.8),
+        (5.0, 0.2),
+        (8.0, 0.7),
+        (9.5, 0.9),
+    ];
+    let spans = |threshold, min_duration| -> Vec<(f64, f64)> {
+        shots(&scores, threshold, min_duration, 10.0)
+            .into_iter()
+            .map(|s| (s.begin, s.end))
+            .collect()
+    };
+    assert_eq!(
+        spans(0.5, 0.0),
+        [(0.0, 2.0), (2.0, 2.5), (2.5, 8.0), (8.0, 9.5), (9.5, 10.0)]
+    );
+    assert_eq!(spans(0.5, 1.0), [(0.0, 2.0), (2.0, 8.0), (8.0, 10.0)]);
+    assert_eq!(spans(0.95, 1.0), [(0.0, 10.0)]);
+}
@@ -0,0 +1,144 @@
+use {
+    crate::{
+        bg_job::BgJob,
+        coords::{Src, VideoDim},
+        ffmpeg::tools::Tools,
+    },
+    egui_sf2g::sf2g::{
+        cpp::FBox,
+        graphics::{IntRect, Texture},
+    },
+    std::{
+        hash::{DefaultHasher, Hash as _, Hasher as _},
+        path::PathBuf,
+    },
+};
+
+const COLS: u32 = 10;
+const ROWS: u32 = 10;
+/// Height of a single thumbnail in the generated tile image
+pub const THUMB_H: u32 = 90;
+
+/// A grid of low-res thumbnails evenly spread over the duration of the source
+#[derive(Default)]
+pub struct Thumbnails {
+    texture: Option<FBox<Texture>>,
+    thumb_w: u32,
+    /// Path the thumbnails were (or are being) generated for
+    path: String,
+    job: Option<BgJob<Vec<u8>>>,
+}
+
+impl Thumbnails {
+    /// Start generating thumbnails in the background, unless they already exist for `path`
+    pub fn generate(&mut self, path: &str, duration: f64, src_dim: VideoDim<Src>, tools: &Tools) {
+        if path == self.path || duration <= 0.0 || src_dim.x <= 0 || src_dim.y <= 0 {
+            return;
+        }
+        self.path = path.to_owned();
+        self.texture = None;
+        // Keep it even, some pixel formats don't like odd sizes
+        self.thumb_w =
+            ((THUMB_H as f64 * src_dim.x as f64 / src_dim.y as f64) as u32 / 2 * 2).max(2);
+        let (path, thumb_w, tools) = (path.to_owned(), self.thumb_w, tools.clone());
+        self.job = Some(BgJob::spawn(move || {
+            load_or_gen_tiles(&tools, &path, duration, thumb_w)
+        }));
+    }
+    /// Upload the generated thumbnails to a texture, if generating finished
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = &mut self.job
+            && let Some(result) = job.poll()
+        {
+            self.job = None;
+            let pixels = result?;
+            let (w, h) = (self.thumb_w * COLS, THUMB_H * ROWS);
+            let Ok(mut texture) = Texture::new() else {
+                anyhow::bail!("Failed to create thumbnail texture");
+            };
+            if texture.create(w, h).is_err() {
+                anyhow::bail!("Failed to create thumbnail texture");
+            }
+            texture.update_from_pixels(&pixels, w, h, 0, 0);
+            self.texture = Some(texture);
+        }
+        Ok(())
+    }
+    #[must_use]
+    pub fn is_generating(&self) -> bool {
+        self.job.is_some()
+    }
+    /// Width of a single thumbnail
+    pub fn thumb_w(&self) -> u32 {
+        self.thumb_w
+    }
+    /// The texture, and the rectangle of the thumbnail closest to `time`
+    pub fn at(&self, time: f64, duration: f64) -> Option<(&Texture, IntRect)> {
+        let texture = self.texture.as_ref()?;
+        let count = COLS * ROWS;
+        let idx = ((time / duration * count as f64) as u32).min(count - 1);
+        let rect = IntRect::new(
+            ((idx % COLS) * self.thumb_w) as i32,
+            ((idx / COLS) * THUMB_H) as i32,
+            self.thumb_w as i32,
+            THUMB_H as i32,
+        );
+        Some((texture, rect))
+    }
+}
+
+fn cache_path(path: &str, thumb_w: u32) -> anyhow::Result<PathBuf> {
+    let meta = std::fs::metadata(path)?;
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    meta.len().hash(&mut hasher);
+    meta.modified()?.hash(&mut hasher);
+    thumb_w.hash(&mut hasher);
+    let dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("No cache dir"))?
+        .join("frogmpeg/thumbs");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{:016x}.rgba", hasher.finish())))
+}
+
+/// Load the raw RGBA tile image from the cache, generating it with ffmpeg if needed
+fn load_or_gen_tiles(
+    tools: &Tools,
+    path: &str,
+    duration: f64,
+    thumb_w: u32,
+) -> anyhow::Result<Vec<u8>> {
+    let expected_len = (thumb_w * COLS * THUMB_H * ROWS * 4) as usize;
+    let cache_path = cache_path(path, thumb_w)?;
+    if let Ok(data) = std::fs::read(&cache_path)
+        && data.len() == expected_len
+    {
+        return Ok(data);
+    }
+    let fps = (COLS * ROWS) as f64 / duration;
+    let out = tools
+        .ffmpeg()
+        .args(["-v", "error", "-skip_frame", "nokey", "-i"])
+        .arg(path)
+        .arg("-vf")
+        .arg(format!(
+            "fps={fps},scale={thumb_w}:{THUMB_H},tile={COLS}x{ROWS}"
+        ))
+        .args(["-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "rgba", "-y"])
+        .arg(&cache_path)
+        .output()?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    let data = std::fs::read(&cache_path)?;
+    if data.len() != expected_len {
+        anyhow::bail!(
+            "Unexpected thumbnail data size: {} (expected {expected_len})",
+            data.len()
+        );
+    }
+    Ok(data)
+}
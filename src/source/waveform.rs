@@ -0,0 +1,163 @@
+use {
+    crate::{bg_job::BgJob, ffmpeg::tools::Tools},
+    std::{io::Read as _, process::Stdio},
+};
+
+/// Sample rate the audio is decoded at. We only need it for drawing, so it can be low.
+const SAMPLE_RATE: u32 = 8000;
+/// Number of samples folded into one peak of the most detailed pyramid level
+const BASE_BUCKET: u32 = 32;
+
+#[derive(Clone, Copy)]
+pub struct Peak {
+    pub min: i16,
+    pub max: i16,
+}
+
+impl Peak {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Min/max peak pyramid of the first audio track of the source
+#[derive(Default)]
+pub struct Waveform {
+    /// Level 0 is the most detailed, each level above has half as many peaks
+    levels: Vec<Vec<Peak>>,
+    job: Option<BgJob<Vec<Peak>>>,
+}
+
+impl Waveform {
+    /// Start decoding the audio of the file at `path` in the background
+    pub fn generate(&mut self, path: &str, tools: &Tools) {
+        self.levels.clear();
+        let (path, tools) = (path.to_owned(), tools.clone());
+        self.job = Some(BgJob::spawn(move || decode_peaks(&tools, &path)));
+    }
+    /// Build the pyramid from the decoded peaks, if decoding finished
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = &mut self.job
+            && let Some(result) = job.poll()
+        {
+            self.job = None;
+            self.levels = build_pyramid(result?);
+        }
+        Ok(())
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    /// The combined peak of the time range `begin..end`
+    pub fn peak(&self, begin: f64, end: f64) -> Option<Peak> {
+        let base_dur = f64::from(BASE_BUCKET) / f64::from(SAMPLE_RATE);
+        // Use the coarsest level whose buckets still fit in the range
+        let mut level = 0;
+        while level + 1 < self.levels.len() && base_dur * (1 << (level + 1)) as f64 <= end - begin {
+            level += 1;
+        }
+        let peaks = self.levels.get(level)?;
+        let bucket_dur = base_dur * (1 << level) as f64;
+        let first = (begin / bucket_dur).max(0.0) as usize;
+        let last = ((end / bucket_dur).max(0.0).ceil() as usize).max(first + 1);
+        peaks
+            .get(first..last.min(peaks.len()))?
+            .iter()
+            .copied()
+            .reduce(Peak::merge)
+    }
+}
+
+fn build_pyramid(base: Vec<Peak>) -> Vec<Vec<Peak>> {
+    let mut levels = vec![base];
+    while let Some(last) = levels.last()
+        && last.len() > 1
+    {
+        let next = last
+            .chunks(2)
+            .map(|pair| pair.iter().copied().reduce(Peak::merge).unwrap())
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Decode the audio to mono 16 bit PCM, and fold it into peaks as it arrives
+fn decode_peaks(tools: &Tools, path: &str) -> anyhow::Result<Vec<Peak>> {
+    let mut child = tools
+        .ffmpeg()
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-ac", "1", "-ar"])
+        .arg(SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    // Drain stderr alongside stdout, so a flood of decode errors can't fill its pipe and block
+    let stderr_reader = std::thread::spawn(move || {
+        let mut err = Vec::new();
+        stderr.read_to_end(&mut err).map(|_| err)
+    });
+    let mut peaks = Vec::new();
+    let mut current: Option<Peak> = None;
+    let mut n_in_bucket = 0;
+    let mut buf = vec![0; 64 * 1024];
+    let mut filled = 0;
+    loop {
+        let n = stdout.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+        let usable = filled / 2 * 2;
+        for &[lo, hi] in buf[..usable].array_chunks::<2>() {
+            let sample = i16::from_le_bytes([lo, hi]);
+            let peak = Peak {
+                min: sample,
+                max: sample,
+            };
+            current = Some(current.map_or(peak, |cur| cur.merge(peak)));
+            n_in_bucket += 1;
+            if n_in_bucket == BASE_BUCKET {
+                peaks.extend(current.take());
+                n_in_bucket = 0;
+            }
+        }
+        buf.copy_within(usable..filled, 0);
+        filled -= usable;
+    }
+    peaks.extend(current);
+    let status = child.wait()?;
+    let err = stderr_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Reading ffmpeg's errors panicked"))??;
+    if !status.success() {
+        anyhow::bail!("ffmpeg failed: {}", String::from_utf8_lossy(&err).trim());
+    }
+    Ok(peaks)
+}
+
+#[test]
+fn test_peak_pyramid() {
+    let peak = |min, max| Peak { min, max };
+    let wf = Waveform {
+        levels: build_pyramid(vec![peak(-1, 1), peak(-5, 2), peak(0, 9), peak(-2, 3)]),
+        job: None,
+    };
+    assert_eq!(wf.levels.len(), 3);
+    let base_dur = f64::from(BASE_BUCKET) / f64::from(SAMPLE_RATE);
+    let p = wf.peak(0.0, base_dur).unwrap();
+    assert_eq!((p.min, p.max), (-1, 1));
+    let p = wf.peak(base_dur, base_dur * 3.0).unwrap();
+    assert_eq!((p.min, p.max), (-5, 9));
+    let p = wf.peak(0.0, base_dur * 4.0).unwrap();
+    assert_eq!((p.min, p.max), (-5, 9));
+    assert!(wf.peak(base_dur * 10.0, base_dur * 11.0).is_none());
+}
@@ -3,19 +3,26 @@ pub mod right_panel;
 
 use {
     crate::{
+        TimeSpan, TimespanMarker,
         app::{AppState, load_kashimark_subs_with_opt_timings},
+        compare::{self, Compare},
         config::{self, Config},
         coords::{VideoMag, VideoPos},
+        ffmpeg::inputs::Input,
         mpv::{
             Mpv,
             commands::LoadFile,
-            properties::{AudioId, Path, Speed, SubId, TimePos, Volume},
+            properties::{
+                AudioId, EstimatedFrameCount, EstimatedFrameNumber, Pause, PictureType, Speed,
+                SubId, TimePos, Volume,
+            },
         },
+        playlist::Playlist,
         time_fmt::FfmpegTimeFmt,
     },
     egui_file_dialog::FileDialog,
     egui_sf2g::egui::{self},
-    ffmpeg_cli::{FfmpegCli, ffmpeg_cli_ui},
+    ffmpeg_cli::{FfmpegCli, OutputRequest, ffmpeg_cli_ui},
     rand::Rng as _,
 };
 
@@ -31,26 +38,50 @@ pub struct UiState {
 #[derive(Default)]
 pub struct ModalPopup {
     payload: Option<ModalPayload>,
+    /// The user agreed to overwrite the files of the last confirmation
+    overwrite_confirmed: bool,
 }
 impl ModalPopup {
     fn err(&mut self, msg: String) {
         self.payload = Some(ModalPayload::Error { msg })
     }
+    fn confirm_overwrite(&mut self, paths: Vec<String>) {
+        self.payload = Some(ModalPayload::ConfirmOverwrite { paths })
+    }
+    /// Whether overwriting was confirmed since the last call
+    fn take_overwrite_confirmed(&mut self) -> bool {
+        std::mem::take(&mut self.overwrite_confirmed)
+    }
 
     fn show(&mut self, ctx: &egui::Context) {
         if let Some(payload) = &self.payload {
-            let mut close = false;
-            egui::Modal::new("modal_popup".into()).show(ctx, |ui| {
-                match payload {
-                    ModalPayload::Error { msg } => {
-                        ui.heading("Error");
-                        ui.label(msg);
+            let (mut close, mut confirmed) = (false, false);
+            egui::Modal::new("modal_popup".into()).show(ctx, |ui| match payload {
+                ModalPayload::Error { msg } => {
+                    ui.heading("Error");
+                    ui.label(msg);
+                    if ui.button("Close").clicked() {
+                        close = true;
                     }
                 }
-                if ui.button("Close").clicked() {
-                    close = true;
+                ModalPayload::ConfirmOverwrite { paths } => {
+                    ui.heading("Overwrite files?");
+                    ui.label("These output files already exist:");
+                    for path in paths {
+                        ui.label(path);
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Overwrite").clicked() {
+                            confirmed = true;
+                            close = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                    });
                 }
             });
+            self.overwrite_confirmed |= confirmed;
             if close {
                 self.payload = None;
             }
@@ -60,12 +91,18 @@ impl ModalPopup {
 
 enum ModalPayload {
     Error { msg: String },
+    ConfirmOverwrite { paths: Vec<String> },
 }
 
 pub enum FileOp {
     MediaFile,
     Kashimark,
     SubTimings,
+    CompareFile,
+    /// Extra input for ffmpeg commands
+    Input,
+    Folder,
+    Playlist,
 }
 
 impl Default for UiState {
@@ -93,33 +130,36 @@ pub(crate) fn ui(
     });
     app_state.video_area_max_dim.y = re.response.rect.top() as VideoMag;
     let re = egui::SidePanel::right("right_panel").show(ctx, |ui| {
-        right_panel::ui(
-            ui,
-            &mut ui_state.right_panel,
-            &mut app_state.source_markers,
-            &mut app_state.interact,
-            &app_state.src,
-            &mut app_state.texts,
-            mpv,
-        );
+        right_panel::ui(ui, &mut ui_state.right_panel, app_state, &cfg.tools, mpv);
     });
     app_state.video_area_max_dim.x = re.response.rect.left() as VideoMag;
     if ui_state.ffmpeg_cli.open {
         egui::Window::new("ffmpeg").show(ctx, |ui| {
-            if let Some(path) = mpv.get_property::<Path>() {
-                app_state.src.path = path.to_owned();
-            }
-            ffmpeg_cli_ui(
-                ui,
-                ui_state,
-                &app_state.source_markers,
-                &app_state.texts,
-                &app_state.src,
-                cfg,
-            );
+            ffmpeg_cli_ui(ui, ui_state, app_state, cfg, mpv);
         });
         ui_state.ffmpeg_cli.first_frame = false;
+    } else {
+        ui_state.ffmpeg_cli.while_closed(mpv);
     }
+    match ui_state.ffmpeg_cli.output_request.take() {
+        Some(OutputRequest::Open(path)) => {
+            cfg.recently_used_list.use_(path.clone());
+            mpv.command_async(LoadFile { path: &path });
+        }
+        Some(OutputRequest::Compare(path)) => open_compare(app_state, ui_state, path),
+        Some(OutputRequest::Preview { path, begin }) => {
+            open_compare(app_state, ui_state, path);
+            if let Some(compare) = &mut app_state.compare {
+                compare.offset = begin;
+                // Play the previewed range, closing the comparison returns to just the source
+                mpv.set_property::<TimePos>(begin);
+                mpv.set_property::<Pause>(false);
+            }
+        }
+        None => {}
+    }
+    // Keep the batch going even when the ffmpeg window is closed
+    ui_state.ffmpeg_cli.batch.poll();
     ui_state.file_dialog.update(ctx);
     if let Some(path) = ui_state.file_dialog.take_picked() {
         match ui_state.file_op {
@@ -146,11 +186,108 @@ pub(crate) fn ui(
                         .err(format!("Error loading sub timings: {e}"));
                 }
             }
+            FileOp::CompareFile => open_compare(app_state, ui_state, path.display().to_string()),
+            FileOp::Folder => open_playlist(mpv, app_state, ui_state, Playlist::from_dir(&path)),
+            FileOp::Playlist => open_playlist(mpv, app_state, ui_state, Playlist::from_file(&path)),
+            FileOp::Input => {
+                let input = Input::new(
+                    path.display().to_string(),
+                    &ui_state.ffmpeg_cli.inputs,
+                    &cfg.tools,
+                );
+                ui_state.ffmpeg_cli.inputs.push(input);
+            }
         }
     }
     ui_state.modal.show(ctx);
 }
 
+fn open_playlist(
+    mpv: &mut Mpv,
+    app_state: &mut AppState,
+    ui_state: &mut UiState,
+    playlist: anyhow::Result<Playlist>,
+) {
+    match playlist {
+        Ok(playlist) => {
+            mpv.command_async(LoadFile {
+                path: &playlist.files[0],
+            });
+            app_state.playlist = playlist;
+        }
+        Err(e) => ui_state.modal.err(format!("Error opening playlist: {e}")),
+    }
+}
+
+fn open_compare(app_state: &mut AppState, ui_state: &mut UiState, path: String) {
+    match Compare::open(path) {
+        Ok(compare) => app_state.compare = Some(compare),
+        Err(e) => ui_state
+            .modal
+            .err(format!("Error opening file to compare: {e}")),
+    }
+}
+
+fn playlist_ui(ui: &mut egui::Ui, mpv: &mut Mpv, app_state: &mut AppState, cfg: &mut Config) {
+    let files = &app_state.playlist.files;
+    let pos = app_state.playlist.position(&app_state.src.path);
+    let mut load = None;
+    if ui
+        .add_enabled(pos.is_some_and(|pos| pos > 0), egui::Button::new("⏮"))
+        .on_hover_text("Previous file")
+        .clicked()
+        && let Some(pos) = pos
+    {
+        load = Some(pos - 1);
+    }
+    ui.label(match pos {
+        Some(pos) => format!("{}/{}", pos + 1, files.len()),
+        None => format!("-/{}", files.len()),
+    });
+    if ui
+        .add_enabled(
+            pos.is_none_or(|pos| pos + 1 < files.len()),
+            egui::Button::new("⏭"),
+        )
+        .on_hover_text("Next file")
+        .clicked()
+    {
+        load = Some(pos.map_or(0, |pos| pos + 1));
+    }
+    ui.menu_button("Files", |ui| {
+        for (i, file) in files.iter().enumerate() {
+            if ui.selectable_label(pos == Some(i), file).clicked() {
+                load = Some(i);
+                ui.close_menu();
+            }
+        }
+    });
+    if ui.button("✖").on_hover_text("Close playlist").clicked() {
+        app_state.playlist = Playlist::default();
+        return;
+    }
+    if let Some(path) = load.and_then(|idx| files.get(idx)) {
+        cfg.recently_used_list.use_(path.clone());
+        mpv.command_async(LoadFile { path });
+    }
+}
+
+fn compare_ui(ui: &mut egui::Ui, app_state: &mut AppState) {
+    let Some(compare) = &mut app_state.compare else {
+        return;
+    };
+    ui.label(format!("Comparing with {}", compare.path));
+    for mode in compare::Mode::ALL {
+        ui.selectable_value(&mut compare.mode, mode, mode.name());
+    }
+    if compare.mode == compare::Mode::Wipe {
+        ui.add(egui::Slider::new(&mut compare.wipe, 0.0..=1.0).show_value(false));
+    }
+    if ui.button("✖").on_hover_text("Stop comparing").clicked() {
+        app_state.compare = None;
+    }
+}
+
 fn bottom_bar_ui(
     ui: &mut egui::Ui,
     ui_state: &mut UiState,
@@ -173,6 +310,15 @@ fn bottom_bar_ui(
             mpv.set_property::<TimePos>(pos);
         }
     });
+    ui.horizontal(|ui| {
+        frame_info_ui(ui, mpv, &app_state.src);
+    });
+    if app_state.compare.is_some() {
+        ui.horizontal(|ui| compare_ui(ui, app_state));
+    }
+    if !app_state.playlist.files.is_empty() {
+        ui.horizontal(|ui| playlist_ui(ui, mpv, app_state, cfg));
+    }
     ui.horizontal(|ui| {
         if let Some(mut speed) = mpv.get_property::<Speed>() {
             ui.label("Playback speed");
@@ -201,6 +347,16 @@ fn bottom_bar_ui(
                 ui_state.file_op = FileOp::MediaFile;
                 ui.close_menu();
             }
+            if ui.button("Open folder...").clicked() {
+                ui_state.file_dialog.pick_directory();
+                ui_state.file_op = FileOp::Folder;
+                ui.close_menu();
+            }
+            if ui.button("Open playlist...").clicked() {
+                ui_state.file_dialog.pick_file();
+                ui_state.file_op = FileOp::Playlist;
+                ui.close_menu();
+            }
             ui.menu_button("Recent", |ui| {
                 for item in cfg.recently_used_list.iter() {
                     if ui.button(item).clicked() {
@@ -211,6 +367,11 @@ fn bottom_bar_ui(
                     }
                 }
             });
+            if ui.button("Compare with file...").clicked() {
+                ui_state.file_dialog.pick_file();
+                ui_state.file_op = FileOp::CompareFile;
+                ui.close_menu();
+            }
             if ui.button("Load kashimark subs...").clicked() {
                 ui_state.file_dialog.pick_file();
                 ui_state.file_op = FileOp::Kashimark;
@@ -344,12 +505,39 @@ fn bottom_bar_ui(
     });
 }
 
+/// Frame number, exact pts, picture type and fps of the current frame
+fn frame_info_ui(ui: &mut egui::Ui, mpv: &Mpv, src_info: &crate::source::Info) {
+    let frame = mpv.get_property::<EstimatedFrameNumber>();
+    let frame_count = mpv.get_property::<EstimatedFrameCount>();
+    match (frame, frame_count) {
+        (Some(frame), Some(count)) => ui.label(format!("Frame {frame}/{count}")),
+        (Some(frame), None) => ui.label(format!("Frame {frame}")),
+        _ => ui.label("Frame -"),
+    };
+    if let Some(pict_type) = mpv.get_property::<PictureType>() {
+        ui.label(format!("({pict_type})"));
+    }
+    ui.separator();
+    // mpv's playback time, relative to the start of the file like `-ss`, not the frame's pts
+    let time = format!("{:.6}", src_info.time_pos);
+    ui.label(format!("time {time}"));
+    if ui.button("copy").clicked() {
+        ui.ctx().copy_text(time);
+    }
+    ui.separator();
+    if src_info.fps > 0.0 {
+        ui.label(format!("{:.3} fps", src_info.fps));
+    } else {
+        ui.label("fps unknown");
+    }
+}
+
 /// Color that works with egui color picker.
 ///
 /// Conversion from rgb255 messes up because of floating point inaccuracies
 pub type EguiFriendlyColor = [f32; 3];
 
-fn random_color() -> EguiFriendlyColor {
+pub(crate) fn random_color() -> EguiFriendlyColor {
     let mut rng = rand::rng();
     [
         rng.random_range(0.1..=1.0),
@@ -357,3 +545,24 @@ fn random_color() -> EguiFriendlyColor {
         rng.random_range(0.1..=1.0),
     ]
 }
+
+/// Add timespan markers named `<prefix><n>`, numbered after the existing ones with that prefix
+pub(crate) fn push_numbered_timespans(
+    timespans: &mut Vec<TimespanMarker>,
+    prefix: &str,
+    spans: Vec<TimeSpan>,
+) {
+    let mut n = timespans
+        .iter()
+        .filter_map(|m| m.name.strip_prefix(prefix)?.parse::<usize>().ok())
+        .max()
+        .map_or(0, |n| n + 1);
+    for timespan in spans {
+        timespans.push(TimespanMarker {
+            timespan,
+            name: format!("{prefix}{n}"),
+            color: random_color(),
+        });
+        n += 1;
+    }
+}
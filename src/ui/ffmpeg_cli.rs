@@ -1,22 +1,54 @@
 use {
-    super::UiState,
+    super::{FileOp, UiState},
     crate::{
-        SourceMarkers,
+        SourceMarkers, TimeSpan,
+        app::AppState,
+        bg_job::BgJob,
         config::{Config, VideoOutPreset},
-        ffmpeg::resolve_arguments,
+        ffmpeg::{
+            audio_filter_chain,
+            batch::{self, Batch},
+            clobbered_inputs, concat, dry_run_result,
+            inputs::Input,
+            loudness::{self, Loudness},
+            mpv_lavfi, output_paths, probe_duration, rect_pix_fmt_warnings,
+            redact::{self, Region},
+            render_preview, resolve_arguments, spawn_dry_run,
+            tools::{self, Detection, Tools},
+            unaligned_copy_cuts, video_filter_chain,
+        },
+        mpv::{
+            Mpv,
+            properties::{Af, Vf},
+        },
         source,
+        time_fmt::FfmpegTimeFmt,
     },
     core::f32,
     egui_sf2g::egui::{self, TextBuffer},
-    std::io::Read as _,
+    std::{
+        io::Read as _,
+        time::{Duration, Instant},
+    },
 };
 
+/// How long typing has to pause before the command is validated
+const VALIDATION_DEBOUNCE: Duration = Duration::from_millis(600);
+
 #[derive(Default)]
 pub struct FfmpegCli {
     pub open: bool,
     pub source_string: String,
     pub first_frame: bool,
+    /// What to do with an output file of a finished job, handled by the main ui
+    pub output_request: Option<OutputRequest>,
+    /// Extra input files, besides the source
+    pub inputs: Vec<Input>,
     child: Option<std::process::Child>,
+    /// Output files of the running job, as detected from the arguments
+    pending_outputs: Vec<String>,
+    /// Output files of the last successful job
+    outputs: Vec<OutputFile>,
     err_str: String,
     exit_status: Option<i32>,
     stdout: String,
@@ -24,12 +56,200 @@ pub struct FfmpegCli {
     optional_content: Option<OptContent>,
     cook_book: CookBook,
     vo_presets: VoPresetsUi,
+    redact: RedactUi,
+    concat: ConcatUi,
+    /// Runs of the command for every file of the playlist, polled by the main ui
+    pub batch: Batch,
+    validation: Validation,
+    preview: Option<PreviewJob>,
+    /// Previews rendered so far, so each one gets its own file.
+    /// The previous one might still be open for comparing.
+    preview_count: u32,
+    live_vf: LiveFilter = LiveFilter::new(false),
+    live_af: LiveFilter = LiveFilter::new(true),
+    loudness: LoudnessUi,
+    setup: SetupUi,
+}
+
+impl FfmpegCli {
+    /// Undo what only applies while the window is open, called by the main ui while it's closed
+    ///
+    /// Live filters and validation come back once the window is open again.
+    pub fn while_closed(&mut self, mpv: &Mpv) {
+        self.live_vf.sync(None, mpv);
+        self.live_af.sync(None, mpv);
+        // Nothing polls the dry run while closed. Forgetting the arguments validates again later.
+        if self.validation.child.is_some() {
+            self.validation.kill();
+            self.validation.args.clear();
+        }
+    }
+}
+
+/// Applying the `-vf` or `-af` chain of the command to playback, through mpv's `vf`/`af` property
+struct LiveFilter {
+    audio: bool,
+    enabled: bool,
+    /// The chain that's currently applied
+    applied: Option<String>,
+    error: Option<String>,
+}
+
+impl LiveFilter {
+    const fn new(audio: bool) -> Self {
+        Self {
+            audio,
+            enabled: false,
+            applied: None,
+            error: None,
+        }
+    }
+    /// Apply `chain` to playback if enabled, otherwise remove the filter
+    fn sync(&mut self, chain: Option<&str>, mpv: &Mpv) {
+        let want = if self.enabled { chain } else { None };
+        if want == self.applied.as_deref() {
+            return;
+        }
+        self.error = None;
+        let ok = match (want, self.audio) {
+            (Some(chain), false) => mpv.set_property::<Vf>(&mpv_lavfi(chain)),
+            (Some(chain), true) => mpv.set_property::<Af>(&mpv_lavfi(chain)),
+            (None, _) => true,
+        };
+        if want.is_none() || !ok {
+            if self.audio {
+                mpv.unset_property::<Af>();
+            } else {
+                mpv.unset_property::<Vf>();
+            }
+        }
+        if !ok {
+            self.error = Some("mpv couldn't apply the filter chain".into());
+        }
+        self.applied = want.map(str::to_owned);
+    }
+    fn ui(&mut self, ui: &mut egui::Ui, label: &str, hover: &str) {
+        ui.checkbox(&mut self.enabled, label).on_hover_text(hover);
+        if let Some(err) = &self.error {
+            ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+        }
+    }
+}
+
+/// Dry runs of the command in the background, while typing
+#[derive(Default)]
+struct Validation {
+    /// The resolved arguments that were last validated (or are being validated)
+    args: Vec<String>,
+    /// When the arguments last changed, so we only validate after typing pauses
+    changed_at: Option<Instant>,
+    /// The running dry run
+    child: Option<std::process::Child>,
+    /// Error line of the last dry run, `Some(None)` if it succeeded
+    result: Option<Option<String>>,
+}
+
+impl Validation {
+    fn kill(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // Also wait for it, so it doesn't linger as a zombie
+            if let Err(e) = child.kill().and_then(|()| child.wait().map(drop)) {
+                eprintln!("Error killing dry run: {e}");
+            }
+        }
+    }
+}
+
+impl Drop for Validation {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+pub enum OutputRequest {
+    Open(String),
+    Compare(String),
+    /// Show a rendered preview next to the source, `begin` being the source time it starts at
+    Preview {
+        path: String,
+        begin: f64,
+    },
+}
+
+/// A preview render of a short range, running in the background
+struct PreviewJob {
+    job: BgJob<()>,
+    path: String,
+    range: TimeSpan,
+}
+
+struct OutputFile {
+    path: String,
+    size: Option<u64>,
+    duration: Option<f64>,
+    probe: BgJob<f64>,
+}
+
+impl OutputFile {
+    fn new(path: String, tools: &Tools) -> Self {
+        let size = std::fs::metadata(&path).ok().map(|meta| meta.len());
+        let (probe_path, tools) = (path.clone(), tools.clone());
+        Self {
+            path,
+            size,
+            duration: None,
+            probe: BgJob::spawn(move || probe_duration(&tools, &probe_path)),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
 enum OptContent {
     CookBook,
     VoPresets,
+    Redact,
+    Loudness,
+    Concat,
+    Inputs,
+    Batch,
+    Setup,
+}
+
+#[derive(Default)]
+struct LoudnessUi {
+    /// Timespan marker to measure, or the whole file
+    timespan: Option<String>,
+    target: loudness::Target,
+    job: Option<BgJob<Loudness>>,
+    result: Option<Loudness>,
+    err: Option<String>,
+}
+
+#[derive(Default)]
+struct RedactUi {
+    style: redact::Style,
+    strength: u16 = 10,
+    /// Rect marker names to redact, with an optional timespan marker name each
+    regions: Vec<Region>,
+}
+
+#[derive(Default)]
+struct ConcatUi {
+    /// Timespan marker names, in the order they are joined
+    timespans: Vec<String>,
+    audio: bool = true,
+    /// Use the concat demuxer instead of a filter graph
+    stream_copy: bool,
+    err: Option<String>,
+}
+
+#[derive(Default)]
+struct SetupUi {
+    job: Option<BgJob<Detection>>,
+    detection: Option<Detection>,
+    err: Option<String>,
+    /// Only list encoders and filters containing this
+    search: String,
 }
 
 #[derive(Default)]
@@ -57,6 +277,7 @@ const fn recipes() -> &'static [Recipe] {
         "Crop video"
         "-vf crop=out_w:out_h:x:y out.mp4";
         "Replace audio track"
+        "-i {i} -i {i.1} -c:v copy -map {n}:v:0 -map {n.1}:a:0 out.mp4"
         "-i video.mp4 -i audio.wav -c:v copy -map 0:v:0 -map 1:a:0 out.mp4";
         "Burn subtitles"
         "-vf subtitles=subtitle.srt"
@@ -71,8 +292,16 @@ struct Recipe {
 
 const FFMPEG_HELP_TEXT: &str = "\
 {i}: Currently opened media file
-{r.x} Rectangle
+{i.stem} {i.ext} {i.dir} {i.name} Parts of the path of the current file
+{out:suffix.ext} Unused path next to the current file, named after it (ext is optional)
+{i.x} Extra input, by name or position (1, 2...)
+{n} {n.x} Input index of -i {i} / -i {i.x}, for -map and filter pads
+{r.x} Rectangle (position of animated rects is an expression of t)
 {t.x} Timespan
+{p.x} Point (x:y)
+{poly.x} Polygon as geq expression (255 inside)
+{c.x} Color (0xRRGGBB)
+{cat.a,b} Timespans joined as a filter graph, outputs [outv][outa] ({catv.a,b}: video only)
 -filter:v \"{x.0}, {x.1} ...\" Draw text(s)
 {v.x} Video output preset
 ";
@@ -80,18 +309,29 @@ const FFMPEG_HELP_TEXT: &str = "\
 pub fn ffmpeg_cli_ui(
     ui: &mut egui::Ui,
     ui_state: &mut UiState,
-    source_markers: &SourceMarkers,
-    texts: &[crate::text::Text],
-    src_info: &source::Info,
+    app_state: &AppState,
     cfg: &mut Config,
+    mpv: &Mpv,
 ) {
+    let (source_markers, texts, src_info) =
+        (&app_state.source_markers, &app_state.texts, &app_state.src);
     if let Some(opt_content) = ui_state.ffmpeg_cli.optional_content {
         egui::SidePanel::right("opt_right_panel").show_inside(ui, |ui| match opt_content {
             OptContent::CookBook => cook_book_ui(ui, ui_state),
             OptContent::VoPresets => vo_presets_ui(ui, ui_state, cfg),
+            OptContent::Redact => redact_ui(ui, ui_state, source_markers),
+            OptContent::Loudness => loudness_ui(ui, ui_state, source_markers, src_info, &cfg.tools),
+            OptContent::Concat => concat_ui(ui, ui_state, source_markers, src_info),
+            OptContent::Inputs => inputs_ui(ui, ui_state),
+            OptContent::Batch => batch_ui(ui, ui_state, app_state, cfg),
+            OptContent::Setup => setup_ui(ui, &mut ui_state.ffmpeg_cli.setup, cfg),
         });
     }
+    for input in &mut ui_state.ffmpeg_cli.inputs {
+        input.poll();
+    }
     let ctrl_enter = ui.input_mut(|inp| inp.consume_key(egui::Modifiers::CTRL, egui::Key::Enter));
+    let overwrite_confirmed = ui_state.modal.take_overwrite_confirmed();
     let re = ui.add(
         egui::TextEdit::multiline(&mut ui_state.ffmpeg_cli.source_string)
             .desired_width(f32::INFINITY)
@@ -102,6 +342,7 @@ pub fn ffmpeg_cli_ui(
         source_markers,
         texts,
         src_info,
+        &ui_state.ffmpeg_cli.inputs,
         cfg,
     ) {
         Ok(args) => {
@@ -110,8 +351,112 @@ pub fn ffmpeg_cli_ui(
                 args_str.push_str(&format!("{i}: `{arg}`\n"));
             }
             ui.label(egui::RichText::new(args_str).color(egui::Color32::GOLD));
-            if ui.button("run (ctrl+enter)").clicked() || ctrl_enter {
+            let unaligned = unaligned_copy_cuts(
+                &ui_state.ffmpeg_cli.source_string,
+                &args,
+                source_markers,
+                src_info,
+            );
+            if !unaligned.is_empty() {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "⚠ Stream copy cuts at keyframes, \
+                         but these timespans don't begin on one: {}",
+                        unaligned.join(", ")
+                    ))
+                    .color(egui::Color32::YELLOW),
+                );
+            }
+            for warning in
+                rect_pix_fmt_warnings(&ui_state.ffmpeg_cli.source_string, &args, source_markers)
+            {
+                ui.label(egui::RichText::new(format!("⚠ {warning}")).color(egui::Color32::YELLOW));
+            }
+            let existing: Vec<String> = output_paths(&args)
+                .into_iter()
+                .filter(|path| cfg.tools.in_working_dir(path).exists())
+                .collect();
+            let clobbered = clobbered_inputs(&args, &src_info.path, &cfg.tools);
+            if !clobbered.is_empty() {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Output would overwrite an input file: {}",
+                        clobbered.join(", ")
+                    ))
+                    .color(egui::Color32::RED),
+                )
+                .on_hover_text("Use {out:suffix.ext} for a path that isn't taken");
+            } else if !existing.is_empty() {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "⚠ These files already exist, running will ask to overwrite them: {}",
+                        existing.join(", ")
+                    ))
+                    .color(egui::Color32::YELLOW),
+                )
+                .on_hover_text("Use {out:suffix.ext} for a path that isn't taken");
+            }
+            validation_ui(ui, &mut ui_state.ffmpeg_cli.validation, &args, &cfg.tools);
+            let cli = &mut ui_state.ffmpeg_cli;
+            ui.horizontal(|ui| {
+                cli.live_vf.ui(
+                    ui,
+                    "live video filter preview",
+                    "Apply the -vf chain to playback. \
+                     Markers won't line up with the video if it crops or scales.",
+                );
+                cli.live_af.ui(
+                    ui,
+                    "live audio filter preview",
+                    "Apply the -af chain to playback",
+                );
+            });
+            cli.live_vf.sync(video_filter_chain(&args), mpv);
+            cli.live_af.sync(audio_filter_chain(&args), mpv);
+            ui.horizontal(|ui| {
+                let range = preview_range(source_markers, src_info);
+                if ui
+                    .add_enabled(
+                        ui_state.ffmpeg_cli.preview.is_none(),
+                        egui::Button::new("preview"),
+                    )
+                    .on_hover_text(format!(
+                        "Quickly render {}-{} and show it next to the source",
+                        FfmpegTimeFmt(range.begin),
+                        FfmpegTimeFmt(range.end)
+                    ))
+                    .clicked()
+                {
+                    ui_state.ffmpeg_cli.preview_count += 1;
+                    let path = std::env::temp_dir()
+                        .join(format!(
+                            "frogmpeg-preview-{}-{}.mkv",
+                            std::process::id(),
+                            ui_state.ffmpeg_cli.preview_count
+                        ))
+                        .display()
+                        .to_string();
+                    let (tools, job_args, job_path) =
+                        (cfg.tools.clone(), args.clone(), path.clone());
+                    ui_state.ffmpeg_cli.preview = Some(PreviewJob {
+                        job: BgJob::spawn(move || {
+                            render_preview(&tools, &job_args, range, &job_path)
+                        }),
+                        path,
+                        range,
+                    });
+                }
+                if ui_state.ffmpeg_cli.preview.is_some() {
+                    ui.spinner();
+                    ui.label("Rendering preview...");
+                }
+            });
+            let run_requested = ui.button("run (ctrl+enter)").clicked() || ctrl_enter;
+            if run_requested && clobbered.is_empty() && !existing.is_empty() {
+                ui_state.modal.confirm_overwrite(existing);
+            } else if run_requested || overwrite_confirmed {
                 ui_state.ffmpeg_cli.exit_status = None;
+                ui_state.ffmpeg_cli.outputs.clear();
                 ui_state.ffmpeg_cli.err_str.clear();
                 ui_state.ffmpeg_cli.stderr.clear();
                 ui_state.ffmpeg_cli.stdout.clear();
@@ -120,16 +465,40 @@ pub fn ffmpeg_cli_ui(
                     source_markers,
                     texts,
                     src_info,
+                    &ui_state.ffmpeg_cli.inputs,
                     cfg,
+                    overwrite_confirmed,
                 ) {
-                    Ok(child) => ui_state.ffmpeg_cli.child = Some(child),
+                    Ok(child) => {
+                        ui_state.ffmpeg_cli.child = Some(child);
+                        ui_state.ffmpeg_cli.pending_outputs = output_paths(&args)
+                            .iter()
+                            .map(|path| cfg.tools.in_working_dir(path).display().to_string())
+                            .collect();
+                    }
                     Err(e) => ui_state.ffmpeg_cli.err_str = e.to_string(),
                 }
             }
         }
         Err(e) => {
             ui.label(egui::RichText::new(e.to_string()).color(egui::Color32::RED));
+            ui_state.ffmpeg_cli.live_vf.sync(None, mpv);
+            ui_state.ffmpeg_cli.live_af.sync(None, mpv);
+        }
+    }
+    if let Some(preview) = &mut ui_state.ffmpeg_cli.preview
+        && let Some(result) = preview.job.poll()
+    {
+        match result {
+            Ok(()) => {
+                ui_state.ffmpeg_cli.output_request = Some(OutputRequest::Preview {
+                    path: preview.path.clone(),
+                    begin: preview.range.begin,
+                });
+            }
+            Err(e) => ui_state.ffmpeg_cli.err_str = e.to_string(),
         }
+        ui_state.ffmpeg_cli.preview = None;
     }
     if ui_state.ffmpeg_cli.first_frame {
         re.request_focus();
@@ -151,6 +520,13 @@ pub fn ffmpeg_cli_ui(
         match child.try_wait() {
             Ok(Some(status)) => {
                 ui_state.ffmpeg_cli.exit_status = status.code();
+                let outputs = std::mem::take(&mut ui_state.ffmpeg_cli.pending_outputs);
+                if status.success() {
+                    ui_state.ffmpeg_cli.outputs = outputs
+                        .into_iter()
+                        .map(|path| OutputFile::new(path, &cfg.tools))
+                        .collect();
+                }
                 if let Some(mut stdout) = child.stdout.take() {
                     let mut buf = Vec::new();
                     stdout.read_to_end(&mut buf).unwrap();
@@ -170,6 +546,7 @@ pub fn ffmpeg_cli_ui(
     if let Some(code) = ui_state.ffmpeg_cli.exit_status {
         ui.label(format!("Exit status: {code}"));
     }
+    outputs_ui(ui, &mut ui_state.ffmpeg_cli);
     if !ui_state.ffmpeg_cli.stdout.is_empty() {
         ui.label("Standard output:");
         egui::ScrollArea::vertical()
@@ -214,7 +591,576 @@ pub fn ffmpeg_cli_ui(
         {
             *opt_c = Some(OptContent::VoPresets);
         }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Redact), "Redact")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Redact);
+        }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Loudness), "Loudness")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Loudness);
+        }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Concat), "Concat")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Concat);
+        }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Inputs), "Inputs")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Inputs);
+        }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Batch), "Batch")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Batch);
+        }
+        if ui
+            .selectable_label(*opt_c == Some(OptContent::Setup), "Setup")
+            .clicked()
+        {
+            *opt_c = Some(OptContent::Setup);
+        }
+    });
+}
+
+/// The timespan marker under the playhead, or a few seconds around the playhead
+fn preview_range(markers: &SourceMarkers, src_info: &source::Info) -> TimeSpan {
+    const AROUND: f64 = 2.0;
+    if let Some(marker) = markers
+        .timespans
+        .iter()
+        .find(|marker| marker.timespan.contains(src_info.time_pos))
+    {
+        return marker.timespan;
+    }
+    TimeSpan {
+        begin: (src_info.time_pos - AROUND).max(0.0),
+        end: (src_info.time_pos + AROUND).min(src_info.duration),
+    }
+}
+
+fn validation_ui(ui: &mut egui::Ui, validation: &mut Validation, args: &[String], tools: &Tools) {
+    if validation.args != args {
+        validation.args = args.to_vec();
+        validation.changed_at = Some(Instant::now());
+        validation.result = None;
+        // A running dry run is for outdated arguments
+        validation.kill();
+    }
+    if let Some(child) = &mut validation.child {
+        let result = match child.try_wait() {
+            Ok(Some(status)) => Some(dry_run_result(child, status)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e.into())),
+        };
+        if let Some(result) = result {
+            validation.result = Some(result.err().map(|e| e.to_string()));
+            validation.child = None;
+        }
+    }
+    if let Some(changed_at) = validation.changed_at
+        && changed_at.elapsed() >= VALIDATION_DEBOUNCE
+    {
+        validation.changed_at = None;
+        match spawn_dry_run(tools, &validation.args) {
+            Ok(child) => validation.child = Some(child),
+            Err(e) => validation.result = Some(Some(e.to_string())),
+        }
+    }
+    ui.horizontal(|ui| match &validation.result {
+        Some(None) => {
+            ui.label(egui::RichText::new("✔ Dry run OK").color(egui::Color32::GREEN));
+        }
+        Some(Some(err)) => {
+            ui.label(egui::RichText::new(format!("✖ {err}")).color(egui::Color32::RED));
+        }
+        None => {
+            if validation.child.is_some() {
+                ui.spinner();
+                ui.label("Validating...");
+            }
+        }
+    });
+}
+
+fn outputs_ui(ui: &mut egui::Ui, cli: &mut FfmpegCli) {
+    for out in &mut cli.outputs {
+        if let Some(result) = out.probe.poll() {
+            match result {
+                Ok(duration) => out.duration = Some(duration),
+                Err(e) => eprintln!("Failed to probe {}: {e}", out.path),
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.link(&out.path).on_hover_text("Open").clicked() {
+                shell_open(&out.path);
+            }
+            if let Some(size) = out.size {
+                ui.label(format_size(size));
+            }
+            if let Some(duration) = out.duration {
+                ui.label(FfmpegTimeFmt(duration).to_string());
+            } else if out.probe.is_running() {
+                ui.spinner();
+            }
+            if ui.button("Open in frogmpeg").clicked() {
+                cli.output_request = Some(OutputRequest::Open(out.path.clone()));
+            }
+            if ui
+                .button("Compare")
+                .on_hover_text("Compare with source")
+                .clicked()
+            {
+                cli.output_request = Some(OutputRequest::Compare(out.path.clone()));
+            }
+            if ui.button("Reveal in folder").clicked() {
+                let path = std::path::Path::new(&out.path);
+                match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => shell_open(dir),
+                    _ => shell_open("."),
+                }
+            }
+        });
+    }
+}
+
+fn shell_open(path: impl AsRef<std::ffi::OsStr>) {
+    if let Err(e) = std::process::Command::new("xdg-open").arg(path).spawn() {
+        eprintln!("Failed to run xdg-open: {e}");
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn loudness_ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut UiState,
+    markers: &SourceMarkers,
+    src_info: &source::Info,
+    tools: &Tools,
+) {
+    let lui = &mut ui_state.ffmpeg_cli.loudness;
+    if let Some(job) = &mut lui.job
+        && let Some(result) = job.poll()
+    {
+        match result {
+            Ok(loudness) => lui.result = Some(loudness),
+            Err(e) => lui.err = Some(e.to_string()),
+        }
+        lui.job = None;
+    }
+    ui.label("EBU R128 loudness, measured with loudnorm");
+    ui.separator();
+    egui::ComboBox::new("loudness_timespan", "Range")
+        .selected_text(lui.timespan.as_deref().unwrap_or("Whole file"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut lui.timespan, None, "Whole file");
+            for span in &markers.timespans {
+                ui.selectable_value(&mut lui.timespan, Some(span.name.clone()), &span.name);
+            }
+        });
+    egui::Grid::new("loudness_target_grid").show(ui, |ui| {
+        ui.label("Target integrated (LUFS)");
+        ui.add(egui::DragValue::new(&mut lui.target.integrated).range(-70.0..=-5.0));
+        ui.end_row();
+        ui.label("Target LRA (LU)");
+        ui.add(egui::DragValue::new(&mut lui.target.lra).range(1.0..=50.0));
+        ui.end_row();
+        ui.label("Target true peak (dBTP)");
+        ui.add(egui::DragValue::new(&mut lui.target.true_peak).range(-9.0..=0.0));
+        ui.end_row();
+    });
+    let range = lui.timespan.as_ref().and_then(|name| {
+        markers
+            .timespans
+            .iter()
+            .find(|m| &m.name == name)
+            .map(|m| m.timespan)
+    });
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(lui.job.is_none(), egui::Button::new("Analyze"))
+            .clicked()
+        {
+            lui.err = None;
+            lui.result = None;
+            let (tools, path, target) = (tools.clone(), src_info.path.clone(), lui.target);
+            lui.job = Some(BgJob::spawn(move || {
+                Loudness::measure(&tools, &path, range, target)
+            }));
+        }
+        if lui.job.is_some() {
+            ui.spinner();
+        }
+    });
+    if let Some(err) = &lui.err {
+        ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+    }
+    let Some(loudness) = lui.result else {
+        return;
+    };
+    egui::Grid::new("loudness_result_grid").show(ui, |ui| {
+        ui.label("Integrated");
+        ui.label(format!("{:.1} LUFS", loudness.integrated));
+        ui.end_row();
+        ui.label("Loudness range");
+        ui.label(format!("{:.1} LU", loudness.lra));
+        ui.end_row();
+        ui.label("True peak");
+        ui.label(format!("{:.1} dBTP", loudness.true_peak));
+        ui.end_row();
+    });
+    let args = loudness.second_pass_args(lui.target, lui.timespan.as_deref());
+    ui.horizontal(|ui| {
+        if ui
+            .button("Use")
+            .on_hover_text(
+                "Second loudnorm pass.\n\
+                 Added to the audio filters of the command, if there is one.",
+            )
+            .clicked()
+        {
+            let template = &mut ui_state.ffmpeg_cli.source_string;
+            if template.trim().is_empty() {
+                *template = args.clone();
+            } else {
+                match loudness.splice_second_pass(lui.target, template) {
+                    Ok(spliced) => *template = spliced,
+                    Err(e) => lui.err = Some(e.to_string()),
+                }
+            }
+        }
+        if ui.button("🏷").on_hover_text("Copy").clicked() {
+            ui.ctx().copy_text(args.clone());
+        }
+    });
+    ui.label(egui::RichText::new(&args).code());
+}
+
+fn batch_ui(ui: &mut egui::Ui, ui_state: &mut UiState, app_state: &AppState, cfg: &Config) {
+    let cli = &mut ui_state.ffmpeg_cli;
+    let files = &app_state.playlist.files;
+    ui.label("Run the command for every file of the playlist, with its own markers");
+    ui.label("Name outputs after the input with {out:suffix.ext}, or {i.dir}, {i.stem}...");
+    ui.separator();
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(
+                !files.is_empty() && !cli.batch.is_running(),
+                egui::Button::new(format!("Run for {} files", files.len())),
+            )
+            .clicked()
+        {
+            let no_markers = SourceMarkers::default();
+            let jobs = files
+                .iter()
+                .map(|path| {
+                    let markers = if *path == app_state.src.path {
+                        &app_state.source_markers
+                    } else {
+                        app_state.playlist.markers(path).unwrap_or(&no_markers)
+                    };
+                    let args = resolve_arguments(
+                        &cli.source_string,
+                        markers,
+                        &app_state.texts,
+                        &source::Info::new(path.clone()),
+                        &cli.inputs,
+                        cfg,
+                    );
+                    batch::Job::new(path.clone(), args, &cfg.tools)
+                })
+                .collect();
+            cli.batch.start(jobs, &cfg.tools);
+        }
+        if cli.batch.is_running() {
+            ui.spinner();
+            ui.checkbox(&mut cli.batch.stop_requested, "Stop after current file");
+        }
+    });
+    if files.is_empty() {
+        ui.label("Open a folder or playlist first");
+    }
+    if cli.batch.jobs.is_empty() {
+        return;
+    }
+    let (done, failed) = cli.batch.counts();
+    ui.label(format!(
+        "{done} succeeded, {failed} failed, {} total",
+        cli.batch.jobs.len()
+    ));
+    egui::ScrollArea::vertical()
+        .max_height(300.0)
+        .show(ui, |ui| {
+            for job in &cli.batch.jobs {
+                ui.horizontal(|ui| {
+                    match &job.status {
+                        batch::Status::Pending => ui.label("…"),
+                        batch::Status::Running => ui.spinner(),
+                        batch::Status::Done => {
+                            ui.label(egui::RichText::new("✔").color(egui::Color32::GREEN))
+                        }
+                        batch::Status::Failed(err) => ui
+                            .label(egui::RichText::new("✖").color(egui::Color32::RED))
+                            .on_hover_text(err),
+                    };
+                    ui.label(&job.path);
+                });
+                if let batch::Status::Failed(err) = &job.status {
+                    ui.label(egui::RichText::new(err).small().color(egui::Color32::RED));
+                }
+            }
+        });
+}
+
+fn inputs_ui(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    ui.label("Extra input files, as {i.name} or {i.1}, {i.2}...");
+    if ui.button("Add...").clicked() {
+        ui_state.file_dialog.pick_file();
+        ui_state.file_op = FileOp::Input;
+    }
+    ui.separator();
+    let mut remove = None;
+    for (i, input) in ui_state.ffmpeg_cli.inputs.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{}.", i + 1));
+            ui.add(egui::TextEdit::singleline(&mut input.name).desired_width(100.0));
+            if ui.button("🏷").on_hover_text("Copy -i argument").clicked() {
+                ui.ctx().copy_text(format!("-i {{i.{}}}", input.name));
+            }
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+        });
+        ui.label(egui::RichText::new(&input.path).small());
+        if input.is_probing() {
+            ui.spinner();
+        } else if let Some(err) = &input.probe_err {
+            ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+        }
+        for stream in &input.streams {
+            ui.label(format!(
+                "#{} {} {}",
+                stream.index, stream.kind, stream.codec
+            ));
+        }
+        ui.separator();
+    }
+    if let Some(idx) = remove {
+        ui_state.ffmpeg_cli.inputs.remove(idx);
+    }
+}
+
+fn concat_ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut UiState,
+    markers: &SourceMarkers,
+    src_info: &source::Info,
+) {
+    let cui = &mut ui_state.ffmpeg_cli.concat;
+    ui.label("Join timespans into one output, in this order");
+    ui.separator();
+    let mut swap = None;
+    let mut remove = None;
+    let len = cui.timespans.len();
+    for (i, name) in cui.timespans.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                swap = Some((i - 1, i));
+            }
+            if ui
+                .add_enabled(i + 1 < len, egui::Button::new("⏷"))
+                .clicked()
+            {
+                swap = Some((i, i + 1));
+            }
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+            ui.label(name);
+        });
+    }
+    if let Some((a, b)) = swap {
+        cui.timespans.swap(a, b);
+    }
+    if let Some(idx) = remove {
+        cui.timespans.remove(idx);
+    }
+    ui.horizontal(|ui| {
+        egui::ComboBox::new("concat_add", "")
+            .selected_text("Add timespan")
+            .show_ui(ui, |ui| {
+                for span in &markers.timespans {
+                    if ui.selectable_label(false, &span.name).clicked() {
+                        cui.timespans.push(span.name.clone());
+                    }
+                }
+            });
+        if ui
+            .button("All, by time")
+            .on_hover_text("Replace the list with every timespan, sorted by begin")
+            .clicked()
+        {
+            let mut spans: Vec<_> = markers.timespans.iter().collect();
+            spans.sort_by(|a, b| a.timespan.begin.total_cmp(&b.timespan.begin));
+            cui.timespans = spans.into_iter().map(|m| m.name.clone()).collect();
+        }
     });
+    // Forget timespans whose markers were deleted or renamed
+    cui.timespans
+        .retain(|name| markers.timespans.iter().any(|m| &m.name == name));
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut cui.stream_copy, false, "Filter graph (re-encode)");
+        ui.selectable_value(&mut cui.stream_copy, true, "Concat demuxer (stream copy)");
+    });
+    if cui.stream_copy {
+        ui.label("Stream copied timespans begin at the keyframe before their begin");
+    } else {
+        ui.checkbox(&mut cui.audio, "Audio");
+    }
+    if cui.timespans.is_empty() {
+        return;
+    }
+    let names = cui.timespans.join(",");
+    let script_path = std::env::temp_dir().join("frogmpeg-concat.txt");
+    let args = if cui.stream_copy {
+        format!(
+            "-f concat -safe 0 -i {} -c copy out.mkv",
+            shell_words::quote(&script_path.display().to_string())
+        )
+    } else if cui.audio {
+        format!("-i {{i}} -filter_complex {{cat.{names}}} -map [outv] -map [outa] out.mp4")
+    } else {
+        format!("-i {{i}} -filter_complex {{catv.{names}}} -map [outv] out.mp4")
+    };
+    ui.horizontal(|ui| {
+        let use_clicked = ui.button("Use").clicked();
+        let copy_clicked = ui.button("🏷").on_hover_text("Copy").clicked();
+        if !(use_clicked || copy_clicked) {
+            return;
+        }
+        cui.err = None;
+        // The demuxer reads the timespans from a script, which has to exist before running
+        if cui.stream_copy
+            && let Err(e) = concat::demuxer_script(&src_info.path, &cui.timespans, markers)
+                .map_err(anyhow::Error::from)
+                .and_then(|script| Ok(std::fs::write(&script_path, script)?))
+        {
+            cui.err = Some(format!("Failed to write concat script: {e}"));
+            return;
+        }
+        if use_clicked {
+            ui_state.ffmpeg_cli.source_string = args.clone();
+        }
+        if copy_clicked {
+            ui.ctx().copy_text(args.clone());
+        }
+    });
+    if let Some(err) = &cui.err {
+        ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+    }
+    ui.label(egui::RichText::new(&args).code());
+}
+
+fn redact_ui(ui: &mut egui::Ui, ui_state: &mut UiState, markers: &SourceMarkers) {
+    let redact_ui = &mut ui_state.ffmpeg_cli.redact;
+    ui.label("Blur or pixelate rects, optionally only during a timespan");
+    ui.separator();
+    ui.horizontal(|ui| {
+        for style in redact::Style::ALL {
+            ui.selectable_value(&mut redact_ui.style, style, style.name());
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label(match redact_ui.style {
+            redact::Style::Blur => "Radius",
+            redact::Style::Pixelate => "Block size",
+        });
+        ui.add(egui::DragValue::new(&mut redact_ui.strength).range(1..=200));
+    });
+    ui.separator();
+    for marker in &markers.rects {
+        let pos = redact_ui.regions.iter().position(|r| r.rect == marker.name);
+        let mut enabled = pos.is_some();
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut enabled, &marker.name).changed() {
+                match pos {
+                    Some(idx) => {
+                        redact_ui.regions.remove(idx);
+                    }
+                    None => redact_ui.regions.push(Region {
+                        rect: marker.name.clone(),
+                        timespan: None,
+                    }),
+                }
+            }
+            let Some(region) = redact_ui.regions.iter_mut().find(|r| r.rect == marker.name) else {
+                return;
+            };
+            egui::ComboBox::from_id_salt(("redact_timespan", &marker.name))
+                .selected_text(region.timespan.as_deref().unwrap_or("Whole video"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut region.timespan, None, "Whole video");
+                    for span in &markers.timespans {
+                        ui.selectable_value(
+                            &mut region.timespan,
+                            Some(span.name.clone()),
+                            &span.name,
+                        );
+                    }
+                });
+        });
+    }
+    // Forget regions whose markers were deleted or renamed
+    redact_ui
+        .regions
+        .retain(|r| markers.rects.iter().any(|m| m.name == r.rect));
+    ui.separator();
+    match redact::filter_complex(
+        &redact_ui.regions,
+        redact_ui.style,
+        redact_ui.strength,
+        markers,
+    ) {
+        Ok(graph) => {
+            let args = format!(
+                "-i {{i}} -filter_complex \"{graph}\" -map \"[out]\" -map 0:a? -c:a copy out.mp4"
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Use").clicked() {
+                    ui_state.ffmpeg_cli.source_string = args.clone();
+                }
+                if ui.button("🏷").on_hover_text("Copy").clicked() {
+                    ui.ctx().copy_text(args.clone());
+                }
+            });
+            ui.label(egui::RichText::new(&args).code());
+        }
+        Err(e) => {
+            ui.label(egui::RichText::new(e.to_string()).color(egui::Color32::RED));
+        }
+    }
 }
 
 fn cook_book_ui(ui: &mut egui::Ui, ui_state: &mut UiState) {
@@ -292,6 +1238,118 @@ fn vo_presets_ui(ui: &mut egui::Ui, ui_state: &mut UiState, cfg: &mut Config) {
     });
 }
 
+fn setup_ui(ui: &mut egui::Ui, sui: &mut SetupUi, cfg: &mut Config) {
+    let settings = &mut cfg.tools;
+    egui::Grid::new("tools_grid").num_columns(2).show(ui, |ui| {
+        ui.label("ffmpeg");
+        ui.text_edit_singleline(&mut settings.ffmpeg);
+        ui.end_row();
+        ui.label("ffprobe");
+        ui.text_edit_singleline(&mut settings.ffprobe);
+        ui.end_row();
+        ui.label("Global args")
+            .on_hover_text("Put before the arguments of commands run from this window");
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.global_args).hint_text("-loglevel warning"),
+        );
+        ui.end_row();
+        ui.label("Working dir")
+            .on_hover_text("Where commands run from this window write relative outputs");
+        ui.add(
+            egui::TextEdit::singleline(&mut settings.working_dir).hint_text("current directory"),
+        );
+        ui.end_row();
+    });
+    if let Err(e) = shell_words::split(&settings.global_args) {
+        ui.label(egui::RichText::new(format!("Global args: {e}")).color(egui::Color32::RED));
+    }
+    ui.label("Environment variables");
+    let mut remove = None;
+    for (i, (name, value)) in settings.env.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(name).desired_width(100.0));
+            ui.label("=");
+            ui.add(egui::TextEdit::singleline(value).desired_width(150.0));
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(idx) = remove {
+        settings.env.remove(idx);
+    }
+    if ui.button("➕").clicked() {
+        settings.env.push(Default::default());
+    }
+    ui.separator();
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(sui.job.is_none(), egui::Button::new("Detect"))
+            .on_hover_text("Find out the version, build configuration, encoders and filters")
+            .clicked()
+        {
+            sui.err = None;
+            let tools = settings.clone();
+            sui.job = Some(BgJob::spawn(move || tools::detect(&tools)));
+        }
+        if sui.job.is_some() {
+            ui.spinner();
+        }
+    });
+    if let Some(job) = &mut sui.job
+        && let Some(result) = job.poll()
+    {
+        sui.job = None;
+        match result {
+            Ok(detection) => sui.detection = Some(detection),
+            Err(e) => sui.err = Some(e.to_string()),
+        }
+    }
+    if let Some(err) = &sui.err {
+        ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+    }
+    let Some(detection) = &sui.detection else {
+        return;
+    };
+    ui.label(&detection.ffmpeg_version);
+    ui.label(&detection.ffprobe_version);
+    egui::CollapsingHeader::new(format!(
+        "Build configuration ({})",
+        detection.configuration.len()
+    ))
+    .show(ui, |ui| {
+        for opt in &detection.configuration {
+            ui.label(egui::RichText::new(opt).monospace());
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Search");
+        ui.text_edit_singleline(&mut sui.search);
+    });
+    for (title, entries) in [
+        ("Encoders", &detection.encoders),
+        ("Filters", &detection.filters),
+    ] {
+        let matching: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.name.contains(&sui.search) || entry.desc.contains(&sui.search))
+            .collect();
+        egui::CollapsingHeader::new(format!("{title} ({})", matching.len())).show(ui, |ui| {
+            egui::ScrollArea::vertical()
+                .id_salt(title)
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for entry in matching {
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(&entry.name).monospace());
+                            ui.label(&entry.desc);
+                        });
+                    }
+                });
+        });
+    }
+}
+
 fn opt_string_edit(ui: &mut egui::Ui, opt_s: &mut Option<String>) {
     match opt_s {
         Some(codec) => {
@@ -1,14 +1,20 @@
 use {
-    super::random_color,
+    super::{push_numbered_timespans, random_color},
     crate::{
-        InteractState, RectDrag, RectMarker, SourceMarkers, TimeSpan, TimespanMarker,
-        coords::VideoRect,
+        ColorMarker, InteractState, Pick, PointMarker, PolygonMarker, RectDrag, RectHandle,
+        RectMarker, SourceMarkers, TimeSpan, TimespanMarker,
+        app::AppState,
+        coords::{Src, VideoMag, VideoPos, VideoRect},
+        ffmpeg::tools::Tools,
         mpv::{
             Mpv,
             properties::{AbLoopA, AbLoopB, TimePos},
         },
-        source,
+        rect_constraints::{ALIGN_CHOICES, RectConstraints},
+        rect_keyframes::{self, Easing, RectKeyframe},
+        source::{self, gaps},
         text::Text,
+        time_fmt::FfmpegTimeFmt,
     },
     egui_sf2g::egui,
 };
@@ -17,38 +23,58 @@ use {
 pub struct State {
     pub tab: Tab = Tab::Rects,
     selected_timespan: Option<usize>,
-    selected_rect: Option<usize>,
+    pub(crate) selected_rect: Option<usize>,
     selected_text: Option<usize>,
+    selected_point: Option<usize>,
+    selected_polygon: Option<usize>,
     rename_index: Option<usize>,
+    /// Snap timespan begin/end to exact frame boundaries when editing
+    snap_to_frames: bool,
+    /// Scene change score above which a new shot begins
+    scene_threshold: f32 = 0.4,
+    /// Shots shorter than this (in seconds) are merged with the next one
+    min_shot_duration: f64 = 1.0,
+    silence_params: gaps::Params = gaps::Params::SILENCE,
+    black_params: gaps::Params = gaps::Params::BLACK,
 }
 
 pub(super) fn ui(
     ui: &mut egui::Ui,
     ui_state: &mut State,
-    source_markers: &mut SourceMarkers,
-    interact_state: &mut InteractState,
-    src_info: &source::Info,
-    texts: &mut Vec<Text>,
+    app_state: &mut AppState,
+    tools: &Tools,
     mpv: &Mpv,
 ) {
+    let AppState {
+        source_markers,
+        interact: interact_state,
+        src: src_info,
+        texts,
+        ..
+    } = app_state;
     ui.horizontal(|ui| {
         ui.selectable_value(&mut ui_state.tab, Tab::Rects, Tab::Rects.name());
         ui.selectable_value(&mut ui_state.tab, Tab::TimeSpans, Tab::TimeSpans.name());
         ui.selectable_value(&mut ui_state.tab, Tab::Texts, Tab::Texts.name());
+        ui.selectable_value(&mut ui_state.tab, Tab::Points, Tab::Points.name());
+        ui.selectable_value(&mut ui_state.tab, Tab::Colors, Tab::Colors.name());
     });
     ui.separator();
     match ui_state.tab {
-        Tab::Rects => rects_ui(ui, source_markers, interact_state, ui_state),
-        Tab::TimeSpans => timespans_ui(ui, source_markers, src_info, ui_state, mpv),
+        Tab::Rects => rects_ui(ui, source_markers, interact_state, ui_state, src_info, mpv),
+        Tab::TimeSpans => timespans_ui(ui, source_markers, src_info, ui_state, tools, mpv),
         Tab::Texts => texts_ui(ui, ui_state, texts, src_info, mpv),
+        Tab::Points => points_ui(ui, source_markers, interact_state, ui_state),
+        Tab::Colors => colors_ui(ui, source_markers, interact_state),
     }
 }
 
 fn timespans_ui(
     ui: &mut egui::Ui,
     markers: &mut SourceMarkers,
-    src_info: &source::Info,
+    src_info: &mut source::Info,
     ui_state: &mut State,
+    tools: &Tools,
     mpv: &Mpv,
 ) {
     if ui.button("Add").clicked() {
@@ -62,6 +88,19 @@ fn timespans_ui(
         });
         ui_state.selected_timespan = Some(markers.timespans.len().saturating_sub(1));
     }
+    ui.checkbox(&mut ui_state.snap_to_frames, "Snap to frames")
+        .on_hover_text(
+            "Snap begin/end to exact frame boundaries, so cuts hit the intended frame.\n\
+             Assumes a constant frame rate, so it's off for variable frame rate sources.",
+        );
+    if src_info.keyframes.is_indexing() {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Indexing keyframes...");
+        });
+    }
+    scene_detection_ui(ui, markers, src_info, ui_state, tools);
+    gap_detection_ui(ui, markers, src_info, ui_state, tools);
     ui.separator();
     egui::ScrollArea::vertical()
         .max_height(300.0)
@@ -98,7 +137,13 @@ fn timespans_ui(
     if let Some(timespan_idx) = ui_state.selected_timespan {
         ui.separator();
         let marker = &mut markers.timespans[timespan_idx];
-        timespan_ui(ui, &mut marker.timespan, src_info, mpv);
+        timespan_ui(
+            ui,
+            &mut marker.timespan,
+            src_info,
+            mpv,
+            ui_state.snap_to_frames,
+        );
 
         if ui.button("Rename (F2)").clicked() || ui.input(|inp| inp.key_pressed(egui::Key::F2)) {
             ui_state.rename_index = Some(timespan_idx);
@@ -131,19 +176,165 @@ fn timespans_ui(
     }
 }
 
-fn timespan_ui(ui: &mut egui::Ui, timespan: &mut TimeSpan, src_info: &source::Info, mpv: &Mpv) {
+fn scene_detection_ui(
+    ui: &mut egui::Ui,
+    markers: &mut SourceMarkers,
+    src_info: &mut source::Info,
+    ui_state: &mut State,
+    tools: &Tools,
+) {
+    egui::CollapsingHeader::new("Scene detection").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            if src_info.scenes.is_detecting() {
+                ui.spinner();
+                ui.label("Detecting scene changes...");
+            } else if ui
+                .add_enabled(!src_info.path.is_empty(), egui::Button::new("Detect"))
+                .clicked()
+            {
+                src_info.scenes.detect(&src_info.path, tools);
+            }
+        });
+        ui.add(
+            egui::Slider::new(&mut ui_state.scene_threshold, 0.0..=1.0)
+                .text("threshold")
+                .max_decimals(2),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Min duration");
+            ui.add(
+                egui::DragValue::new(&mut ui_state.min_shot_duration)
+                    .range(0.0..=f64::INFINITY)
+                    .speed(0.05)
+                    .suffix(" s"),
+            );
+        });
+        if src_info.scenes.scores.is_empty() {
+            return;
+        }
+        let shots = source::scenes::shots(
+            &src_info.scenes.scores,
+            ui_state.scene_threshold,
+            ui_state.min_shot_duration,
+            src_info.duration,
+        );
+        ui.label(format!("{} shots", shots.len()));
+        if ui.button("Create timespan markers").clicked() {
+            push_numbered_timespans(&mut markers.timespans, "shot", shots);
+        }
+    });
+}
+
+fn gap_detection_ui(
+    ui: &mut egui::Ui,
+    markers: &mut SourceMarkers,
+    src_info: &mut source::Info,
+    ui_state: &mut State,
+    tools: &Tools,
+) {
+    egui::CollapsingHeader::new("Silence / black detection").show(ui, |ui| {
+        for kind in [gaps::Kind::Silence, gaps::Kind::Black] {
+            let (detected, params) = match kind {
+                gaps::Kind::Silence => (&mut src_info.silence, &mut ui_state.silence_params),
+                gaps::Kind::Black => (&mut src_info.black, &mut ui_state.black_params),
+            };
+            ui.push_id(kind.name(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(kind.name());
+                    if detected.is_detecting() {
+                        ui.spinner();
+                    } else if ui
+                        .add_enabled(!src_info.path.is_empty(), egui::Button::new("Detect"))
+                        .clicked()
+                    {
+                        detected.detect(&src_info.path, kind, *params, src_info.duration, tools);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    match kind {
+                        gaps::Kind::Silence => {
+                            ui.label("Noise");
+                            ui.add(
+                                egui::DragValue::new(&mut params.threshold)
+                                    .range(-90.0..=0.0)
+                                    .suffix(" dB"),
+                            );
+                        }
+                        gaps::Kind::Black => {
+                            ui.label("Pixel threshold");
+                            ui.add(
+                                egui::DragValue::new(&mut params.threshold)
+                                    .range(0.0..=1.0)
+                                    .speed(0.01),
+                            );
+                        }
+                    }
+                    ui.label("Min duration");
+                    ui.add(
+                        egui::DragValue::new(&mut params.min_duration)
+                            .range(0.0..=f64::INFINITY)
+                            .speed(0.05)
+                            .suffix(" s"),
+                    );
+                });
+                if detected.spans.is_empty() {
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} intervals", detected.spans.len()));
+                    if ui.button("To markers").clicked() {
+                        push_numbered_timespans(
+                            &mut markers.timespans,
+                            kind.name(),
+                            detected.spans.clone(),
+                        );
+                    }
+                    if ui
+                        .button("Everything else to markers")
+                        .on_hover_text(
+                            "Create markers for the parts to keep, e.g. to cut out dead air",
+                        )
+                        .clicked()
+                    {
+                        let keep = gaps::complement(&detected.spans, src_info.duration);
+                        push_numbered_timespans(&mut markers.timespans, "keep", keep);
+                    }
+                });
+            });
+            ui.separator();
+        }
+    });
+}
+
+fn timespan_ui(
+    ui: &mut egui::Ui,
+    timespan: &mut TimeSpan,
+    src_info: &source::Info,
+    mpv: &Mpv,
+    snap: bool,
+) {
     egui::Grid::new("begin_end_grid").show(ui, |ui| {
         ui.label("begin");
-        ui.add(egui::DragValue::new(&mut timespan.begin));
+        ui.add(egui::DragValue::new(&mut timespan.begin).max_decimals(6));
         if ui.button("=").on_hover_text("Set to current").clicked() {
             timespan.begin = src_info.time_pos;
         }
+        if ui
+            .add_enabled(!src_info.keyframes.times.is_empty(), egui::Button::new("⏮"))
+            .on_hover_text("Snap to previous keyframe")
+            .clicked()
+            && let Some(kf) = src_info
+                .keyframes
+                .prev(timespan.begin + src_info.keyframe_tolerance())
+        {
+            timespan.begin = kf;
+        }
         if ui.button("▶").on_hover_text("Seek here").clicked() {
             mpv.set_property::<TimePos>(timespan.begin);
         }
         ui.end_row();
         ui.label("end");
-        ui.add(egui::DragValue::new(&mut timespan.end));
+        ui.add(egui::DragValue::new(&mut timespan.end).max_decimals(6));
         if ui.button("=").on_hover_text("Set to current").clicked() {
             timespan.end = src_info.time_pos;
         }
@@ -161,6 +352,10 @@ fn timespan_ui(ui: &mut egui::Ui, timespan: &mut TimeSpan, src_info: &source::In
             ui.ctx().copy_text(dur_s);
         }
     });
+    if snap {
+        timespan.begin = src_info.snap_to_frame(timespan.begin);
+        timespan.end = src_info.snap_to_frame(timespan.end);
+    }
 }
 
 fn rects_ui(
@@ -168,12 +363,16 @@ fn rects_ui(
     markers: &mut SourceMarkers,
     interact_state: &mut InteractState,
     ui_state: &mut State,
+    src_info: &source::Info,
+    mpv: &Mpv,
 ) {
     if ui.button("Add").clicked() {
         markers.rects.push(RectMarker {
             rect: VideoRect::new(0, 0, 0, 0),
             name: format!("{}", markers.rects.len()),
             color: random_color(),
+            constraints: RectConstraints::default(),
+            keyframes: Vec::new(),
         });
     }
     ui.separator();
@@ -199,6 +398,7 @@ fn rects_ui(
                     if ui_state.selected_rect == Some(i) {
                         ui_state.selected_rect = None;
                     }
+                    interact_state.active_rect = None;
                     retain = false;
                 }
             });
@@ -208,17 +408,48 @@ fn rects_ui(
         if let Some(idx) = ui_state.selected_rect {
             ui.separator();
             let marker = &mut markers.rects[idx];
+            // Which numeric edit happened, so constraints know which side leads
+            let mut edited = None;
             egui::Grid::new("rects_grid").show(ui, |ui| {
                 ui.label("x");
-                ui.add(egui::DragValue::new(&mut marker.rect.pos.x));
+                if ui
+                    .add(egui::DragValue::new(&mut marker.rect.pos.x))
+                    .changed()
+                {
+                    edited = Some(RectHandle::Body);
+                }
                 ui.label("y");
-                ui.add(egui::DragValue::new(&mut marker.rect.pos.y));
+                if ui
+                    .add(egui::DragValue::new(&mut marker.rect.pos.y))
+                    .changed()
+                {
+                    edited = Some(RectHandle::Body);
+                }
                 ui.end_row();
                 ui.label("w");
-                ui.add(egui::DragValue::new(&mut marker.rect.dim.x));
+                if ui
+                    .add(egui::DragValue::new(&mut marker.rect.dim.x))
+                    .changed()
+                {
+                    edited = Some(RectHandle::Right);
+                }
                 ui.label("h");
-                ui.add(egui::DragValue::new(&mut marker.rect.dim.y));
+                if ui
+                    .add(egui::DragValue::new(&mut marker.rect.dim.y))
+                    .changed()
+                {
+                    edited = Some(RectHandle::Bottom);
+                }
             });
+            if constraints_ui(ui, &mut marker.constraints, marker.rect) {
+                edited = Some(RectHandle::Right);
+            }
+            if let Some(handle) = edited {
+                marker.rect = marker
+                    .constraints
+                    .apply(marker.rect, src_info.dim, Some(handle));
+            }
+            rect_keyframes_ui(ui, marker, src_info, mpv);
             if ui
                 .add_enabled(
                     interact_state.rect_drag.is_none(),
@@ -236,11 +467,176 @@ fn rects_ui(
     });
 }
 
+fn rect_keyframes_ui(
+    ui: &mut egui::Ui,
+    marker: &mut RectMarker,
+    src_info: &source::Info,
+    mpv: &Mpv,
+) {
+    egui::CollapsingHeader::new(format!("Keyframes ({})", marker.keyframes.len()))
+        .id_salt("rect_keyframes")
+        .show(ui, |ui| {
+            if ui
+                .button("◆ Set keyframe")
+                .on_hover_text(
+                    "Store the current rect at the current time.\n\
+                     Its size is applied to every keyframe, only the position is animated.",
+                )
+                .clicked()
+            {
+                rect_keyframes::set(
+                    &mut marker.keyframes,
+                    RectKeyframe {
+                        time: src_info.time_pos,
+                        rect: marker.rect,
+                        easing: Easing::default(),
+                    },
+                    src_info.keyframe_tolerance(),
+                );
+                // ffmpeg's crop only evaluates the size once, so it can't follow a changing size
+                for kf in &mut marker.keyframes {
+                    kf.rect.dim = marker.rect.dim;
+                }
+            }
+            if let Some(dim) = marker.keyframes.first().map(|kf| kf.rect.dim)
+                && marker
+                    .keyframes
+                    .iter()
+                    .any(|kf| (kf.rect.dim.x, kf.rect.dim.y) != (dim.x, dim.y))
+            {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "⚠ Keyframes have different sizes, ffmpeg commands use {}x{}",
+                            dim.x, dim.y
+                        ))
+                        .color(egui::Color32::YELLOW),
+                    );
+                    if ui.button("Use for all").clicked() {
+                        for kf in &mut marker.keyframes {
+                            kf.rect.dim = dim;
+                        }
+                    }
+                });
+            }
+            let mut remove = None;
+            egui::Grid::new("rect_keyframes_grid").show(ui, |ui| {
+                for (i, kf) in marker.keyframes.iter_mut().enumerate() {
+                    if ui.button("▶").on_hover_text("Seek here").clicked() {
+                        mpv.set_property::<TimePos>(kf.time);
+                    }
+                    ui.label(FfmpegTimeFmt(kf.time).to_string());
+                    ui.label(format!(
+                        "{}x{} @ {},{}",
+                        kf.rect.dim.x, kf.rect.dim.y, kf.rect.pos.x, kf.rect.pos.y
+                    ));
+                    egui::ComboBox::from_id_salt(("rect_kf_easing", i))
+                        .selected_text(kf.easing.name())
+                        .show_ui(ui, |ui| {
+                            for easing in Easing::ALL {
+                                ui.selectable_value(&mut kf.easing, easing, easing.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Interpolation towards the next keyframe");
+                    if ui.button("🗑").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                marker.keyframes.remove(i);
+            }
+        });
+}
+
+/// Returns whether any of the constraints changed
+fn constraints_ui(
+    ui: &mut egui::Ui,
+    constraints: &mut RectConstraints,
+    rect: VideoRect<Src>,
+) -> bool {
+    let mut changed = false;
+    egui::CollapsingHeader::new("Constraints")
+        .id_salt("rect_constraints")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                let mut locked = constraints.aspect.is_some();
+                if ui.checkbox(&mut locked, "Lock aspect").changed() {
+                    constraints.aspect = locked.then(|| {
+                        // Start out with the current aspect ratio, if there is one
+                        match (u16::try_from(rect.dim.x), u16::try_from(rect.dim.y)) {
+                            (Ok(w), Ok(h)) if w > 0 && h > 0 => (w, h),
+                            _ => (16, 9),
+                        }
+                    });
+                    changed = true;
+                }
+                if let Some((w, h)) = &mut constraints.aspect {
+                    changed |= ui
+                        .add(egui::DragValue::new(w).range(1..=u16::MAX))
+                        .changed();
+                    ui.label(":");
+                    changed |= ui
+                        .add(egui::DragValue::new(h).range(1..=u16::MAX))
+                        .changed();
+                }
+            });
+            ui.horizontal(|ui| {
+                for (w, h) in [(16, 9), (9, 16), (1, 1), (4, 3), (4, 5)] {
+                    if ui.button(format!("{w}:{h}")).clicked() {
+                        constraints.aspect = Some((w, h));
+                        changed = true;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Round to multiple of");
+                for align in ALIGN_CHOICES {
+                    let label = if align == 1 {
+                        "off".to_string()
+                    } else {
+                        align.to_string()
+                    };
+                    changed |= ui
+                        .selectable_value(&mut constraints.align, align, label)
+                        .changed();
+                }
+            });
+            egui::Grid::new("constraints_size_grid").show(ui, |ui| {
+                ui.label("Min w/h");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut constraints.min_w).range(0..=VideoMag::MAX))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut constraints.min_h).range(0..=VideoMag::MAX))
+                    .changed();
+                ui.end_row();
+                ui.label("Max w/h");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut constraints.max_w).range(0..=VideoMag::MAX))
+                    .on_hover_text("0 means unlimited")
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut constraints.max_h).range(0..=VideoMag::MAX))
+                    .on_hover_text("0 means unlimited")
+                    .changed();
+            });
+            changed |= ui
+                .checkbox(&mut constraints.clamp_to_frame, "Clamp to frame")
+                .changed();
+        });
+    changed
+}
+
 #[derive(PartialEq, Eq)]
 pub enum Tab {
     Rects,
     TimeSpans,
     Texts,
+    Points,
+    Colors,
 }
 
 impl Tab {
@@ -249,10 +645,183 @@ impl Tab {
             Tab::Rects => "Rects",
             Tab::TimeSpans => "Time spans",
             Tab::Texts => "Texts",
+            Tab::Points => "Points",
+            Tab::Colors => "Colors",
+        }
+    }
+}
+
+fn points_ui(
+    ui: &mut egui::Ui,
+    markers: &mut SourceMarkers,
+    interact_state: &mut InteractState,
+    ui_state: &mut State,
+) {
+    ui.heading("Points");
+    if ui.button("Add").clicked() {
+        markers.points.push(PointMarker {
+            pos: VideoPos::new(0, 0),
+            name: format!("{}", markers.points.len()),
+            color: random_color(),
+        });
+    }
+    let mut i = 0;
+    markers.points.retain_mut(|marker| {
+        let mut retain = true;
+        ui.horizontal(|ui| {
+            egui::color_picker::color_edit_button_rgb(ui, &mut marker.color);
+            if ui
+                .selectable_label(ui_state.selected_point == Some(i), &marker.name)
+                .clicked()
+            {
+                ui_state.selected_point = Some(i);
+            }
+            if ui.button("🗑").clicked() {
+                shift_after_remove(&mut ui_state.selected_point, i);
+                if let Some(Pick::Point(idx)) = interact_state.pick {
+                    let mut pick = Some(idx);
+                    shift_after_remove(&mut pick, i);
+                    interact_state.pick = pick.map(Pick::Point);
+                }
+                retain = false;
+            }
+        });
+        i += 1;
+        retain
+    });
+    if let Some(idx) = ui_state.selected_point {
+        let marker = &mut markers.points[idx];
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut marker.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut marker.pos.x));
+            ui.label("y");
+            ui.add(egui::DragValue::new(&mut marker.pos.y));
+        });
+        if interact_state.pick == Some(Pick::Point(idx)) {
+            ui.label("Click on the video to place the point (Esc cancels)");
+        } else if ui.button("pick with mouse").clicked() {
+            interact_state.pick = Some(Pick::Point(idx));
+        }
+    }
+    ui.separator();
+    ui.heading("Polygons");
+    if ui.button("Add").clicked() {
+        markers.polygons.push(PolygonMarker {
+            points: Vec::new(),
+            name: format!("{}", markers.polygons.len()),
+            color: random_color(),
+        });
+    }
+    let mut i = 0;
+    markers.polygons.retain_mut(|marker| {
+        let mut retain = true;
+        ui.horizontal(|ui| {
+            egui::color_picker::color_edit_button_rgb(ui, &mut marker.color);
+            if ui
+                .selectable_label(ui_state.selected_polygon == Some(i), &marker.name)
+                .clicked()
+            {
+                ui_state.selected_polygon = Some(i);
+            }
+            if ui.button("🗑").clicked() {
+                shift_after_remove(&mut ui_state.selected_polygon, i);
+                if let Some(Pick::PolygonVertices(idx)) = interact_state.pick {
+                    let mut pick = Some(idx);
+                    shift_after_remove(&mut pick, i);
+                    interact_state.pick = pick.map(Pick::PolygonVertices);
+                }
+                retain = false;
+            }
+        });
+        i += 1;
+        retain
+    });
+    if let Some(idx) = ui_state.selected_polygon {
+        let marker = &mut markers.polygons[idx];
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut marker.name);
+        });
+        let mut remove = None;
+        egui::Grid::new("polygon_vertices_grid").show(ui, |ui| {
+            for (i, pos) in marker.points.iter_mut().enumerate() {
+                ui.label(format!("{i}"));
+                ui.add(egui::DragValue::new(&mut pos.x));
+                ui.add(egui::DragValue::new(&mut pos.y));
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            marker.points.remove(i);
+        }
+        if interact_state.pick == Some(Pick::PolygonVertices(idx)) {
+            ui.label("Click on the video to add vertices");
+            if ui.button("Stop").clicked() {
+                interact_state.pick = None;
+            }
+        } else if ui.button("add vertices with mouse").clicked() {
+            interact_state.pick = Some(Pick::PolygonVertices(idx));
         }
     }
 }
 
+/// Keep an index into a list pointing at the same item after the one at `removed` is deleted
+fn shift_after_remove(idx: &mut Option<usize>, removed: usize) {
+    match *idx {
+        Some(i) if i == removed => *idx = None,
+        Some(i) if i > removed => *idx = Some(i - 1),
+        _ => {}
+    }
+}
+
+fn colors_ui(ui: &mut egui::Ui, markers: &mut SourceMarkers, interact_state: &mut InteractState) {
+    ui.label("Use these with {c.name}");
+    if ui.button("Add").clicked() {
+        markers.colors.push(ColorMarker {
+            rgb: [255, 255, 255],
+            name: format!("{}", markers.colors.len()),
+        });
+        interact_state.pick = Some(Pick::Color(markers.colors.len() - 1));
+    }
+    if let Some(Pick::Color(_)) = interact_state.pick {
+        ui.label("Click on the video to pick a color (Esc cancels)");
+    }
+    ui.separator();
+    let mut i = 0;
+    markers.colors.retain_mut(|marker| {
+        let mut retain = true;
+        ui.horizontal(|ui| {
+            egui::color_picker::color_edit_button_srgb(ui, &mut marker.rgb);
+            ui.add(egui::TextEdit::singleline(&mut marker.name).desired_width(80.0));
+            ui.label(egui::RichText::new(crate::color::ffmpeg_hex(marker.rgb)).monospace());
+            if ui
+                .selectable_label(interact_state.pick == Some(Pick::Color(i)), "🎯")
+                .on_hover_text("Pick from video")
+                .clicked()
+            {
+                interact_state.pick = Some(Pick::Color(i));
+            }
+            if ui.button("🗑").clicked() {
+                if let Some(Pick::Color(idx)) = interact_state.pick {
+                    let mut pick = Some(idx);
+                    shift_after_remove(&mut pick, i);
+                    interact_state.pick = pick.map(Pick::Color);
+                }
+                retain = false;
+            }
+        });
+        i += 1;
+        retain
+    });
+}
+
 fn texts_ui(
     ui: &mut egui::Ui,
     ui_state: &mut State,
@@ -321,6 +890,12 @@ fn texts_ui(
         ui.text_edit_multiline(&mut text.string);
         ui.separator();
         ui.heading("Timespan");
-        timespan_ui(ui, &mut text.timespan, src_info, mpv);
+        timespan_ui(
+            ui,
+            &mut text.timespan,
+            src_info,
+            mpv,
+            ui_state.snap_to_frames,
+        );
     }
 }